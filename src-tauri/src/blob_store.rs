@@ -0,0 +1,191 @@
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+/// 计算内容哈希（SHA-256 十六进制），作为 blob 的地址
+pub fn content_hash(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+// 本地内容寻址的二进制数据存储，用于保存图片等不适合放进日志文件的大块数据
+#[derive(Debug, Clone)]
+pub struct BlobStore {
+    dir: PathBuf,
+}
+
+impl BlobStore {
+    pub fn new(dir: PathBuf) -> Result<Self, Box<dyn std::error::Error>> {
+        std::fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    // blob 文件路径，按哈希前两位分目录，避免单个目录文件过多
+    pub fn path(&self, hash: &str) -> PathBuf {
+        let prefix = hash.get(..2).unwrap_or("00");
+        self.dir.join(prefix).join(hash)
+    }
+
+    // 以已知哈希写入数据（例如从远端下载的 blob），写入前校验哈希
+    pub fn put_with_hash(&self, hash: &str, data: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        if content_hash(data) != hash {
+            return Err(format!("blob 哈希不匹配: {}", hash).into());
+        }
+
+        let path = self.path(hash);
+        if path.exists() {
+            return Ok(());
+        }
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        // 先写临时文件再重命名，避免中途崩溃留下不完整的 blob
+        let temp_path = path.with_extension("tmp");
+        std::fs::write(&temp_path, data)?;
+        std::fs::rename(&temp_path, &path)?;
+        Ok(())
+    }
+
+    // 边读边计算哈希，把文件内容写入 blob 存储，返回内容哈希
+    pub fn put_file(&self, source: &Path) -> Result<String, Box<dyn std::error::Error>> {
+        let mut reader = std::fs::File::open(source)?;
+        let temp_path = self.dir.join(format!("{}.tmp", uuid::Uuid::new_v4()));
+        let mut writer = std::io::BufWriter::new(std::fs::File::create(&temp_path)?);
+        let mut hasher = Sha256::new();
+        let mut buffer = vec![0; 64 * 1024];
+
+        let result = (|| -> Result<String, Box<dyn std::error::Error>> {
+            loop {
+                let read = reader.read(&mut buffer)?;
+                if read == 0 {
+                    break;
+                }
+                hasher.update(&buffer[..read]);
+                writer.write_all(&buffer[..read])?;
+            }
+            writer.flush()?;
+            Ok(format!("{:x}", hasher.finalize()))
+        })();
+
+        let hash = match result {
+            Ok(hash) => hash,
+            Err(e) => {
+                let _ = std::fs::remove_file(&temp_path);
+                return Err(e);
+            }
+        };
+
+        let path = self.path(&hash);
+        if path.exists() {
+            std::fs::remove_file(&temp_path)?;
+            return Ok(hash);
+        }
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::rename(&temp_path, &path)?;
+        Ok(hash)
+    }
+
+    pub fn get(&self, hash: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        Ok(std::fs::read(self.path(hash))?)
+    }
+
+    // 删除未被引用的 blob，返回删除的数量
+    //
+    // 正在写入的临时文件不删除；并发写入时文件可能已被重命名，找不到的文件直接跳过
+    pub fn retain(&self, referenced: &HashSet<String>) -> Result<usize, Box<dyn std::error::Error>> {
+        let mut removed = 0;
+        for prefix_entry in std::fs::read_dir(&self.dir)? {
+            let prefix_dir = prefix_entry?.path();
+            if !prefix_dir.is_dir() {
+                continue;
+            }
+            for entry in std::fs::read_dir(&prefix_dir)? {
+                let path = entry?.path();
+                let name = file_name(&path);
+                if referenced.contains(name) || name.ends_with(".tmp") {
+                    continue;
+                }
+                match std::fs::remove_file(&path) {
+                    Ok(()) => removed += 1,
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                    Err(e) => return Err(e.into()),
+                }
+            }
+        }
+        Ok(removed)
+    }
+
+    // 所有 blob 占用的总字节数
+    pub fn total_size(&self) -> u64 {
+        let mut total = 0;
+        if let Ok(prefixes) = std::fs::read_dir(&self.dir) {
+            for prefix_dir in prefixes.flatten() {
+                if let Ok(entries) = std::fs::read_dir(prefix_dir.path()) {
+                    total += entries
+                        .flatten()
+                        .filter_map(|entry| entry.metadata().ok())
+                        .map(|metadata| metadata.len())
+                        .sum::<u64>();
+                }
+            }
+        }
+        total
+    }
+}
+
+fn file_name(path: &Path) -> &str {
+    path.file_name().and_then(|name| name.to_str()).unwrap_or("")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn put_and_get_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = BlobStore::new(dir.path().join("blobs")).unwrap();
+
+        let hash = content_hash(b"image data");
+        store.put_with_hash(&hash, b"image data").unwrap();
+        assert_eq!(store.get(&hash).unwrap(), b"image data");
+        assert!(store.path(&hash).starts_with(dir.path().join("blobs").join(&hash[..2])));
+        // 哈希不匹配的数据不写入
+        assert!(store.put_with_hash(&content_hash(b"other"), b"image data").is_err());
+        assert!(store.get(&content_hash(b"other")).is_err());
+
+        let source = dir.path().join("source.bin");
+        std::fs::write(&source, b"file data").unwrap();
+        let file_hash = store.put_file(&source).unwrap();
+        assert_eq!(file_hash, content_hash(b"file data"));
+        assert_eq!(store.get(&file_hash).unwrap(), b"file data");
+        // 重复写入同样的内容得到同一个 blob
+        assert_eq!(store.put_file(&source).unwrap(), file_hash);
+        assert_eq!(store.total_size(), ("image data".len() + "file data".len()) as u64);
+    }
+
+    #[test]
+    fn retain_removes_only_unreferenced_blobs() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = BlobStore::new(dir.path().to_path_buf()).unwrap();
+
+        let kept = content_hash(b"kept");
+        let dropped = content_hash(b"dropped");
+        store.put_with_hash(&kept, b"kept").unwrap();
+        store.put_with_hash(&dropped, b"dropped").unwrap();
+        // 正在写入的临时文件
+        let pending = content_hash(b"pending");
+        let temp_path = store.path(&pending).with_extension("tmp");
+        std::fs::create_dir_all(temp_path.parent().unwrap()).unwrap();
+        std::fs::write(&temp_path, b"pending").unwrap();
+
+        let referenced = HashSet::from([kept.clone()]);
+        assert_eq!(store.retain(&referenced).unwrap(), 1);
+        assert!(store.get(&kept).is_ok());
+        assert!(store.get(&dropped).is_err());
+        assert!(temp_path.exists());
+    }
+}
@@ -0,0 +1,89 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// 剪贴板记录状态，保存在应用数据目录的 capture_state.json 中，重启后保持
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CaptureState {
+    /// 是否已暂停记录
+    pub paused: bool,
+    /// 无痕模式的结束时间（Unix 秒），到期后自动恢复记录
+    pub incognito_until: Option<u64>,
+}
+
+impl CaptureState {
+    /// 当前是否应该记录剪贴板
+    pub fn is_capturing(&self, now: u64) -> bool {
+        if !self.paused {
+            return true;
+        }
+        // 无痕模式到期后视为已恢复
+        matches!(self.incognito_until, Some(until) if now >= until)
+    }
+
+    /// 暂停记录（不自动恢复）
+    pub fn paused() -> Self {
+        Self {
+            paused: true,
+            incognito_until: None,
+        }
+    }
+
+    /// 进入无痕模式，minutes 分钟后自动恢复；时长过大时一直持续到 u64::MAX
+    pub fn incognito(now: u64, minutes: u64) -> Self {
+        Self {
+            paused: true,
+            incognito_until: Some(now.saturating_add(minutes.saturating_mul(60))),
+        }
+    }
+
+    /// 从文件加载状态，文件不存在或无法解析时视为正在记录
+    pub fn load_or_default(path: &Path) -> Self {
+        match std::fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                tracing::warn!("解析记录状态失败，恢复记录: {}", e);
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    /// 保存状态到文件
+    pub fn save_to_file(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let content = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, content).context("Failed to save capture state")?;
+        Ok(())
+    }
+}
+
+/// 发送给前端的记录状态
+#[derive(Debug, Clone, Serialize)]
+pub struct CaptureStatus {
+    pub paused: bool,
+    pub incognito_until: Option<u64>,
+    /// 监听线程是否在运行
+    pub watcher_running: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn incognito_resumes_after_duration() {
+        let state = CaptureState::incognito(1_000, 15);
+        assert_eq!(state.incognito_until, Some(1_900));
+        assert!(!state.is_capturing(1_899));
+        assert!(state.is_capturing(1_900));
+    }
+
+    #[test]
+    fn incognito_duration_does_not_overflow() {
+        let state = CaptureState::incognito(1_000, u64::MAX / 2);
+        assert_eq!(state.incognito_until, Some(u64::MAX));
+        assert!(!state.is_capturing(u64::MAX - 1));
+    }
+}
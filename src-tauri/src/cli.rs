@@ -0,0 +1,428 @@
+//! clippy-cli：在终端中查看和管理剪贴板历史
+//!
+//! 与 GUI 使用同一个应用数据目录。只读命令可以在 GUI 运行时直接执行；
+//! 修改历史的命令需要获取应用数据目录的锁，GUI 运行期间会被拒绝。
+//! 所有输出均为 JSON，错误以 {"error": "..."} 的形式输出到标准错误，退出码为 1。
+
+use serde::Serialize;
+use serde_json::json;
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use crate::instance_lock::InstanceLock;
+use crate::storage::{ClipboardItem, StorageEngine};
+use crate::storage_adapter::StorageConfig;
+#[cfg(not(target_os = "linux"))]
+use crate::ClipboardStorage;
+use crate::{get_app_data_dir, hide_sensitive_fields, HistoryQuery};
+
+const USAGE: &str = "用法: clippy-cli <命令> [参数]
+
+命令:
+  list [--limit N] [--type text|files|image] [--kind KIND]   列出历史项目（最新的在前）
+  search <关键字> [--limit N]                                搜索内容包含关键字的项目
+  show <id>                                                  显示单个项目
+  copy <id> [--plain]                                        把项目写入剪贴板（Linux 下需要 Clippy 正在运行）
+  delete <id>                                                删除项目
+  clear                                                      清空历史
+  export <文件>                                              导出历史为 JSON
+  import <文件>                                              从 JSON 导入历史，跳过已存在的项目
+  compact                                                    压缩存储文件
+  stats                                                      存储统计
+  sync-now                                                   立即与远端同步
+  watch [--event NAME]                                       持续输出运行中的 Clippy 的事件（每行一个 JSON）
+  config [show]                                              显示存储配置（隐藏密钥）
+  config set <文件>                                          验证并保存存储配置";
+
+type CliResult = Result<serde_json::Value, String>;
+
+/// 命令行入口，返回进程退出码
+pub fn main() -> i32 {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if matches!(args.first().map(String::as_str), None | Some("help" | "--help" | "-h")) {
+        println!("{}", USAGE);
+        return if args.is_empty() { 2 } else { 0 };
+    }
+
+    match run(&args) {
+        Ok(output) => {
+            print_json(&output);
+            0
+        }
+        Err(e) => {
+            eprintln!("{}", json!({ "error": e }));
+            1
+        }
+    }
+}
+
+fn run(args: &[String]) -> CliResult {
+    let Some((command, rest)) = args.split_first() else {
+        return Err(USAGE.to_string());
+    };
+    let args = Args::parse(rest)?;
+
+    match command.as_str() {
+        "list" => list(&args),
+        "search" => search(&args),
+        "show" => show(&args),
+        "copy" => copy(&args),
+        "delete" => delete(&args),
+        "clear" => clear(),
+        "export" => export(&args),
+        "import" => import(&args),
+        "compact" => compact(),
+        "stats" => stats(),
+        "sync-now" => sync_now(),
+        "watch" => watch(&args),
+        "config" => config(&args),
+        other => Err(format!("未知命令: {}\n\n{}", other, USAGE)),
+    }
+}
+
+// 位置参数和 --name value 形式的选项
+struct Args {
+    positional: Vec<String>,
+    options: Vec<(String, Option<String>)>,
+}
+
+// 不带值的开关选项
+const FLAGS: &[&str] = &["plain"];
+
+impl Args {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut positional = Vec::new();
+        let mut options = Vec::new();
+        let mut iter = args.iter();
+
+        while let Some(arg) = iter.next() {
+            match arg.strip_prefix("--") {
+                Some(name) if FLAGS.contains(&name) => options.push((name.to_string(), None)),
+                Some(name) => {
+                    let value = iter.next().ok_or_else(|| format!("选项 --{} 缺少值", name))?;
+                    options.push((name.to_string(), Some(value.clone())));
+                }
+                None => positional.push(arg.clone()),
+            }
+        }
+        Ok(Self { positional, options })
+    }
+
+    fn positional(&self, index: usize, name: &str) -> Result<&str, String> {
+        self.positional
+            .get(index)
+            .map(String::as_str)
+            .ok_or_else(|| format!("缺少参数: {}", name))
+    }
+
+    fn option(&self, name: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .find(|(option, _)| option == name)
+            .and_then(|(_, value)| value.as_deref())
+    }
+
+    fn flag(&self, name: &str) -> bool {
+        self.options.iter().any(|(option, _)| option == name)
+    }
+
+    fn limit(&self) -> Result<Option<usize>, String> {
+        self.option("limit")
+            .map(|limit| limit.parse().map_err(|_| format!("无效的数量: {}", limit)))
+            .transpose()
+    }
+}
+
+fn print_json(value: &serde_json::Value) {
+    match serde_json::to_string_pretty(value) {
+        // 输出被管道提前关闭（例如 | head）时忽略错误
+        Ok(output) => {
+            let _ = writeln!(std::io::stdout(), "{}", output);
+        }
+        Err(e) => eprintln!("{}", json!({ "error": e.to_string() })),
+    }
+}
+
+fn to_json<T: Serialize>(value: &T) -> CliResult {
+    serde_json::to_value(value).map_err(|e| e.to_string())
+}
+
+// 打开存储引擎只读取数据，不写入存储文件，可以和 GUI 同时运行
+fn open_storage() -> Result<StorageEngine, String> {
+    StorageEngine::new(get_app_data_dir()).map_err(|e| format!("打开存储失败: {}", e))
+}
+
+// 获取应用数据目录的锁后打开存储引擎，用于修改历史的命令
+fn open_storage_exclusive() -> Result<(InstanceLock, StorageEngine), String> {
+    let lock = InstanceLock::try_acquire(&get_app_data_dir())
+        .map_err(|e| format!("获取存储锁失败: {}", e))?
+        .ok_or_else(|| "Clippy 正在运行，请先退出 GUI 再执行修改历史的命令".to_string())?;
+    Ok((lock, open_storage()?))
+}
+
+fn get_item(storage: &StorageEngine, item_id: &str) -> Result<ClipboardItem, String> {
+    storage.get(item_id).ok_or_else(|| format!("项目不存在: {}", item_id))
+}
+
+fn list(args: &Args) -> CliResult {
+    let query = HistoryQuery {
+        item_type: args.option("type").map(String::from),
+        kind: args.option("kind").map(String::from),
+        limit: args.limit()?,
+        ..Default::default()
+    };
+    to_json(&query.run(&open_storage()?))
+}
+
+fn search(args: &Args) -> CliResult {
+    let query = HistoryQuery {
+        search: Some(args.positional(0, "关键字")?.to_string()),
+        limit: args.limit()?,
+        ..Default::default()
+    };
+    to_json(&query.run(&open_storage()?))
+}
+
+fn show(args: &Args) -> CliResult {
+    let storage = open_storage()?;
+    to_json(&get_item(&storage, args.positional(0, "id")?)?)
+}
+
+// GUI 运行时交给 GUI 写入剪贴板：GUI 能识别自己的写入，不会记录为新的复制，
+// 并且在 X11/Wayland 下命令行退出后剪贴板内容仍然有效
+fn copy(args: &Args) -> CliResult {
+    let item_id = args.positional(0, "id")?;
+    let plain = args.flag("plain");
+    let Some(_lock) = InstanceLock::try_acquire(&get_app_data_dir()).map_err(|e| format!("获取存储锁失败: {}", e))? else {
+        return copy_via_gui(item_id, plain);
+    };
+    copy_directly(item_id, plain)
+}
+
+// X11/Wayland 下剪贴板内容由持有选区的进程提供，命令行退出后内容随之失效，
+// 直接写入等于什么都没做
+#[cfg(target_os = "linux")]
+fn copy_directly(item_id: &str, _plain: bool) -> CliResult {
+    get_item(&open_storage()?, item_id)?;
+    Err("Clippy 没有运行：X11/Wayland 下命令行退出后写入的剪贴板内容会失效，请先启动 Clippy 再执行 copy".to_string())
+}
+
+// 没有 GUI 在监听剪贴板，直接写入不会产生重复记录；进程退出后内容仍由系统保留
+#[cfg(not(target_os = "linux"))]
+fn copy_directly(item_id: &str, plain: bool) -> CliResult {
+    let storage: ClipboardStorage = Arc::new(Mutex::new(open_storage()?));
+    crate::write_item_to_clipboard(item_id, &storage, &crate::loopback::WriteRegistry::default(), plain)?;
+    Ok(json!({ "copied": item_id }))
+}
+
+#[cfg(unix)]
+fn copy_via_gui(item_id: &str, plain: bool) -> CliResult {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    runtime.block_on(async {
+        let mut client = crate::ipc::IpcClient::connect(&get_app_data_dir())
+            .await
+            .map_err(|e| format!("Clippy 正在运行，但无法连接自动化接口: {:#}", e))?;
+        client
+            .call("copy", json!({ "id": item_id, "plain_text": plain }))
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(json!({ "copied": item_id }))
+    })
+}
+
+#[cfg(not(unix))]
+fn copy_via_gui(_item_id: &str, _plain: bool) -> CliResult {
+    Err("Clippy 正在运行，请在 GUI 中复制".to_string())
+}
+
+fn delete(args: &Args) -> CliResult {
+    let item_id = args.positional(0, "id")?;
+    let (_lock, mut storage) = open_storage_exclusive()?;
+    let item = get_item(&storage, item_id)?;
+    storage.delete(item_id).map_err(|e| e.to_string())?;
+    Ok(with_sync_result(json!({ "deleted": item_id }), sync_deletes(&[item])))
+}
+
+fn clear() -> CliResult {
+    let (_lock, mut storage) = open_storage_exclusive()?;
+    let removed = storage.clear_all().map_err(|e| e.to_string())?;
+    Ok(with_sync_result(json!({ "deleted": removed.len() }), sync_deletes(&removed)))
+}
+
+// GUI 没有运行时由命令行生成删除操作并上传，未配置同步存储时跳过；
+// 上传失败的操作保留在发件箱中，下次同步时继续上传
+fn sync_deletes(items: &[ClipboardItem]) -> Result<(), String> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    runtime.block_on(async {
+        match crate::create_sync_engine().await {
+            Some(sync_engine) => crate::push_local_deletes(&sync_engine, items).await,
+            None => Ok(()),
+        }
+    })
+}
+
+// 本地删除已经成功，同步失败只在结果中说明
+fn with_sync_result(mut result: serde_json::Value, sync: Result<(), String>) -> serde_json::Value {
+    if let Err(e) = sync {
+        result["sync_error"] = json!(e);
+    }
+    result
+}
+
+fn export(args: &Args) -> CliResult {
+    let path = args.positional(0, "文件")?;
+    let items = open_storage()?.get_all();
+    let content = serde_json::to_string_pretty(&items).map_err(|e| e.to_string())?;
+    std::fs::write(path, content).map_err(|e| format!("写入文件失败: {}", e))?;
+    Ok(json!({ "exported": items.len(), "path": path }))
+}
+
+fn import(args: &Args) -> CliResult {
+    let path = args.positional(0, "文件")?;
+    let content = std::fs::read_to_string(path).map_err(|e| format!("读取文件失败: {}", e))?;
+    let items: Vec<ClipboardItem> = serde_json::from_str(&content).map_err(|e| format!("解析文件失败: {}", e))?;
+
+    let (_lock, mut storage) = open_storage_exclusive()?;
+    let mut imported = 0;
+    for item in &items {
+        if storage.get(&item.id).is_some() {
+            continue;
+        }
+        storage.insert(item).map_err(|e| e.to_string())?;
+        imported += 1;
+    }
+    Ok(json!({ "imported": imported, "skipped": items.len() - imported }))
+}
+
+fn compact() -> CliResult {
+    let (_lock, mut storage) = open_storage_exclusive()?;
+    storage.compact().map_err(|e| e.to_string())?;
+    to_json(&storage.stats())
+}
+
+fn stats() -> CliResult {
+    to_json(&open_storage()?.stats())
+}
+
+// 同步发件箱保存在应用数据目录中，GUI 运行时交给 GUI 同步，避免两个进程同时上传
+fn sync_now() -> CliResult {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    let Some(_lock) = InstanceLock::try_acquire(&get_app_data_dir()).map_err(|e| format!("获取存储锁失败: {}", e))? else {
+        return runtime.block_on(sync_via_gui());
+    };
+
+    // 其他设备的变化写入本地历史，和 GUI 中的同步一致
+    let storage = Arc::new(Mutex::new(open_storage()?));
+    let changes = Arc::new(Mutex::new((0, 0)));
+
+    let mut status = runtime.block_on(async {
+        let sync_engine = crate::create_sync_engine()
+            .await
+            .ok_or_else(|| "未配置同步存储".to_string())?;
+        let (storage, changes) = (storage.clone(), changes.clone());
+        sync_engine.on_remote_changes(move |delta| {
+            let applied = crate::apply_remote_changes(&mut storage.lock().unwrap(), &delta);
+            let mut changes = changes.lock().unwrap();
+            changes.0 += applied.added.len();
+            changes.1 += applied.removed.len();
+        });
+        sync_engine.sync_now().await.map_err(|e| e.to_string())?;
+        sync_engine.get_status().await.map_err(|e| e.to_string())
+    })?;
+
+    let (added, removed) = *changes.lock().unwrap();
+    status["added"] = json!(added);
+    status["removed"] = json!(removed);
+    Ok(status)
+}
+
+#[cfg(unix)]
+async fn sync_via_gui() -> CliResult {
+    let mut client = crate::ipc::IpcClient::connect(&get_app_data_dir())
+        .await
+        .map_err(|e| format!("Clippy 正在运行，但无法连接自动化接口: {:#}", e))?;
+    client.call("sync_now", serde_json::Value::Null).await.map_err(|e| e.to_string())?;
+    Ok(json!({ "synced": true }))
+}
+
+#[cfg(not(unix))]
+async fn sync_via_gui() -> CliResult {
+    Err("Clippy 正在运行，请在 GUI 中同步".to_string())
+}
+
+// 通过自动化接口订阅事件，直到 GUI 退出或连接断开
+#[cfg(unix)]
+fn watch(args: &Args) -> CliResult {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    runtime.block_on(async {
+        let mut client = crate::ipc::IpcClient::connect(&get_app_data_dir())
+            .await
+            .map_err(|e| format!("无法连接 Clippy，请确认 GUI 正在运行: {:#}", e))?;
+        let events: Vec<&str> = args.option("event").into_iter().collect();
+        client.subscribe(&events).await.map_err(|e| e.to_string())?;
+
+        loop {
+            let event = client.next_event().await.map_err(|e| e.to_string())?;
+            let line = json!({ "event": event.event, "payload": event.payload });
+            if writeln!(std::io::stdout(), "{}", line).is_err() {
+                // 输出被关闭时正常结束
+                return Ok(json!({ "watching": false }));
+            }
+        }
+    })
+}
+
+#[cfg(not(unix))]
+fn watch(_args: &Args) -> CliResult {
+    Err("当前平台不支持 watch".to_string())
+}
+
+fn config(args: &Args) -> CliResult {
+    let config_file = get_app_data_dir().join("storage_config.json");
+    match args.positional.first().map(String::as_str) {
+        None | Some("show") => {
+            let config = StorageConfig::load_from_file(config_file.to_string_lossy().as_ref())
+                .map_err(|e| format!("未配置同步存储: {}", e))?;
+            let mut value = to_json(&config)?;
+            if let Some(backend) = value.get_mut("backend") {
+                hide_sensitive_fields(backend);
+            }
+            Ok(value)
+        }
+        Some("set") => {
+            let path = args.positional(1, "文件")?;
+            let content = std::fs::read_to_string(Path::new(path)).map_err(|e| format!("读取文件失败: {}", e))?;
+            let config: StorageConfig = serde_json::from_str(&content).map_err(|e| format!("无效的存储配置: {}", e))?;
+
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .map_err(|e| e.to_string())?;
+            runtime.block_on(config.validate()).map_err(|e| e.to_string())?;
+
+            config
+                .save_to_file(config_file.to_string_lossy().as_ref())
+                .map_err(|e| e.to_string())?;
+            // GUI 在下次启动或重新配置存储时才会使用新配置
+            Ok(json!({ "saved": config_file }))
+        }
+        Some(other) => Err(format!("未知的 config 子命令: {}", other)),
+    }
+}
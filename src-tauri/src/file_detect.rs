@@ -0,0 +1,167 @@
+use std::fs::{File, Metadata};
+use std::io::Read;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use crate::file_types::{FileTypeEntry, FileTypeRegistry, UNKNOWN_CATEGORY};
+use crate::storage::FileTypeInfo;
+
+/// 内容检测最多读取的文件头字节数，足够 infer 识别所有支持的格式
+const HEADER_LEN: u64 = 8192;
+
+/// 统计目录时最多遍历的条目数，超过后停止并标记为不完整
+const MAX_DIR_ENTRIES: u64 = 100_000;
+
+/// 并行检测的最大线程数
+const MAX_WORKERS: usize = 4;
+
+/// 并行检测多个文件的类型，结果顺序与输入一致
+pub fn detect_file_types(paths: &[String], registry: &FileTypeRegistry) -> Vec<FileTypeInfo> {
+    let workers = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+        .min(MAX_WORKERS)
+        .min(paths.len());
+
+    if workers <= 1 {
+        return paths.iter().map(|path| detect_file_type(path, registry)).collect();
+    }
+
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<FileTypeInfo>>> = Mutex::new(vec![None; paths.len()]);
+
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(path) = paths.get(index) else {
+                    break;
+                };
+                let info = detect_file_type(path, registry);
+                results.lock().unwrap()[index] = Some(info);
+            });
+        }
+    });
+
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .zip(paths)
+        .map(|(info, path)| info.unwrap_or_else(|| detect_file_type(path, registry)))
+        .collect()
+}
+
+/// 检测单个文件的类型，只读取文件头，不会把整个文件读入内存
+pub fn detect_file_type(file_path: &str, registry: &FileTypeRegistry) -> FileTypeInfo {
+    let path = Path::new(file_path);
+    let extension = path.extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+
+    let is_symlink = std::fs::symlink_metadata(path)
+        .map(|metadata| metadata.file_type().is_symlink())
+        .unwrap_or(false);
+
+    // 符号链接按其指向的目标检测
+    let metadata = match std::fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(_) => {
+            // 文件不存在或链接已失效，只能根据扩展名猜测
+            let resolved = if is_symlink {
+                FileTypeEntry {
+                    mime_type: "inode/symlink".to_string(),
+                    category: UNKNOWN_CATEGORY.to_string(),
+                }
+            } else {
+                registry.resolve(&extension, None)
+            };
+            return FileTypeInfo {
+                path: file_path.to_string(),
+                file_type: extension,
+                mime_type: resolved.mime_type,
+                category: resolved.category,
+                is_symlink,
+                ..Default::default()
+            };
+        }
+    };
+
+    if metadata.is_dir() {
+        let (size, entry_count, truncated) = directory_stats(path);
+        return FileTypeInfo {
+            path: file_path.to_string(),
+            file_type: String::new(),
+            mime_type: "inode/directory".to_string(),
+            category: "folder".to_string(),
+            size,
+            is_dir: true,
+            is_symlink,
+            entry_count: Some(entry_count),
+            truncated,
+            ..Default::default()
+        };
+    }
+
+    // 根据文件头和扩展名确定 MIME 类型和类别
+    let detected_mime = detect_mime_from_header(path, &metadata);
+    let resolved = registry.resolve(&extension, detected_mime);
+
+    FileTypeInfo {
+        path: file_path.to_string(),
+        file_type: extension,
+        mime_type: resolved.mime_type,
+        category: resolved.category,
+        size: metadata.len(),
+        is_symlink,
+        ..Default::default()
+    }
+}
+
+// 读取文件头并识别 MIME 类型；空文件和非普通文件（设备、管道等）不读取
+fn detect_mime_from_header(path: &Path, metadata: &Metadata) -> Option<&'static str> {
+    if !metadata.is_file() || metadata.len() == 0 {
+        return None;
+    }
+
+    let file = File::open(path).ok()?;
+    let mut header = Vec::with_capacity(HEADER_LEN.min(metadata.len()) as usize);
+    file.take(HEADER_LEN).read_to_end(&mut header).ok()?;
+
+    infer::get(&header).map(|kind| kind.mime_type())
+}
+
+// 递归统计目录大小和条目数，不跟随目录内的符号链接以避免循环
+// 返回 (总字节数, 条目数, 是否因条目过多而提前停止)
+fn directory_stats(dir: &Path) -> (u64, u64, bool) {
+    let mut size = 0;
+    let mut entries = 0;
+    let mut pending = vec![dir.to_path_buf()];
+
+    while let Some(current) = pending.pop() {
+        let read_dir = match std::fs::read_dir(&current) {
+            Ok(read_dir) => read_dir,
+            Err(_) => continue,
+        };
+
+        for entry in read_dir.flatten() {
+            entries += 1;
+            if entries > MAX_DIR_ENTRIES {
+                return (size, entries - 1, true);
+            }
+
+            let Ok(metadata) = std::fs::symlink_metadata(entry.path()) else {
+                continue;
+            };
+            if metadata.is_dir() {
+                pending.push(entry.path());
+            } else {
+                size += metadata.len();
+            }
+        }
+    }
+
+    (size, entries, false)
+}
@@ -0,0 +1,78 @@
+use std::path::{Path, PathBuf};
+
+use crate::blob_store::BlobStore;
+use crate::settings::FileSnapshotSettings;
+use crate::storage::FileTypeInfo;
+
+/// 为复制的文件保留副本，副本地址记录在 FileTypeInfo::snapshot 中
+///
+/// 目录不保留副本；超过单文件上限的文件跳过，累计大小超过总上限后不再保留。
+pub fn snapshot_files(file_types: &mut [FileTypeInfo], blobs: &BlobStore, settings: &FileSnapshotSettings) {
+    let mut total = 0;
+    for info in file_types.iter_mut() {
+        if info.is_dir || info.size > settings.max_file_size {
+            continue;
+        }
+        if total + info.size > settings.max_total_size {
+            break;
+        }
+
+        // 检测后文件可能已被修改，以实际读取的内容为准
+        match blobs.put_file(Path::new(&info.path)) {
+            Ok(hash) => {
+                total += info.size;
+                info.snapshot = Some(hash);
+            }
+            Err(e) => eprintln!("保留文件副本失败 {}: {}", info.path, e),
+        }
+    }
+}
+
+/// 确定写入剪贴板的文件路径，原文件不存在时从副本恢复到 restore_dir 下
+pub fn resolve_file_paths(
+    file_paths: &[String],
+    file_types: &[FileTypeInfo],
+    blobs: &BlobStore,
+    restore_dir: &Path,
+) -> Result<Vec<String>, String> {
+    file_paths
+        .iter()
+        .map(|path| {
+            if Path::new(path).exists() {
+                return Ok(path.clone());
+            }
+
+            let hash = file_types
+                .iter()
+                .find(|info| &info.path == path)
+                .and_then(|info| info.snapshot.as_deref())
+                .ok_or_else(|| format!("文件不存在: {}", path))?;
+
+            restore_file(path, hash, blobs, restore_dir)
+                .map(|restored| restored.to_string_lossy().into_owned())
+                .map_err(|e| format!("恢复文件副本失败 {}: {}", path, e))
+        })
+        .collect()
+}
+
+// 按哈希分目录恢复，保留原文件名；同一副本已恢复过时直接复用
+fn restore_file(
+    original: &str,
+    hash: &str,
+    blobs: &BlobStore,
+    restore_dir: &Path,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let file_name = Path::new(original)
+        .file_name()
+        .ok_or("无效的文件路径")?;
+    let target = restore_dir.join(hash.get(..16).unwrap_or(hash)).join(file_name);
+    if target.exists() {
+        return Ok(target);
+    }
+
+    if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::copy(blobs.path(hash), &target)?;
+    Ok(target)
+}
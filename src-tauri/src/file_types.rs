@@ -0,0 +1,263 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// 未知类型使用的 MIME
+pub const UNKNOWN_MIME: &str = "application/octet-stream";
+
+/// 未知类型使用的类别
+pub const UNKNOWN_CATEGORY: &str = "other";
+
+// 内置的扩展名表：(扩展名, MIME 类型, 类别)
+const BUILTIN_TYPES: &[(&str, &str, &str)] = &[
+    // 图片
+    ("png", "image/png", "image"),
+    ("jpg", "image/jpeg", "image"),
+    ("jpeg", "image/jpeg", "image"),
+    ("gif", "image/gif", "image"),
+    ("bmp", "image/bmp", "image"),
+    ("webp", "image/webp", "image"),
+    ("svg", "image/svg+xml", "image"),
+    ("ico", "image/vnd.microsoft.icon", "image"),
+    ("tif", "image/tiff", "image"),
+    ("tiff", "image/tiff", "image"),
+    ("heic", "image/heic", "image"),
+    ("heif", "image/heif", "image"),
+    ("avif", "image/avif", "image"),
+    // 视频
+    ("mp4", "video/mp4", "video"),
+    ("m4v", "video/x-m4v", "video"),
+    ("mov", "video/quicktime", "video"),
+    ("mkv", "video/x-matroska", "video"),
+    ("webm", "video/webm", "video"),
+    ("avi", "video/x-msvideo", "video"),
+    // 音频
+    ("mp3", "audio/mpeg", "audio"),
+    ("wav", "audio/x-wav", "audio"),
+    ("flac", "audio/x-flac", "audio"),
+    ("ogg", "audio/ogg", "audio"),
+    ("m4a", "audio/m4a", "audio"),
+    ("aac", "audio/aac", "audio"),
+    // 文本
+    ("txt", "text/plain", "text"),
+    ("md", "text/markdown", "text"),
+    ("markdown", "text/markdown", "text"),
+    ("csv", "text/csv", "text"),
+    ("log", "text/plain", "text"),
+    ("html", "text/html", "text"),
+    ("htm", "text/html", "text"),
+    ("css", "text/css", "text"),
+    // 文档
+    ("pdf", "application/pdf", "document"),
+    ("doc", "application/msword", "document"),
+    ("docx", "application/vnd.openxmlformats-officedocument.wordprocessingml.document", "document"),
+    ("xls", "application/vnd.ms-excel", "document"),
+    ("xlsx", "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet", "document"),
+    ("ppt", "application/vnd.ms-powerpoint", "document"),
+    ("pptx", "application/vnd.openxmlformats-officedocument.presentationml.presentation", "document"),
+    ("odt", "application/vnd.oasis.opendocument.text", "document"),
+    ("ods", "application/vnd.oasis.opendocument.spreadsheet", "document"),
+    ("odp", "application/vnd.oasis.opendocument.presentation", "document"),
+    ("rtf", "application/rtf", "document"),
+    ("epub", "application/epub+zip", "document"),
+    // 压缩包
+    ("zip", "application/zip", "archive"),
+    ("rar", "application/vnd.rar", "archive"),
+    ("7z", "application/x-7z-compressed", "archive"),
+    ("tar", "application/x-tar", "archive"),
+    ("gz", "application/gzip", "archive"),
+    ("bz2", "application/x-bzip2", "archive"),
+    ("xz", "application/x-xz", "archive"),
+    ("zst", "application/zstd", "archive"),
+    // 代码和配置
+    ("js", "text/javascript", "code"),
+    ("mjs", "text/javascript", "code"),
+    ("ts", "text/x-typescript", "code"),
+    ("tsx", "text/x-typescript", "code"),
+    ("jsx", "text/javascript", "code"),
+    ("py", "text/x-python", "code"),
+    ("java", "text/x-java", "code"),
+    ("c", "text/x-c", "code"),
+    ("h", "text/x-c", "code"),
+    ("cpp", "text/x-c++", "code"),
+    ("hpp", "text/x-c++", "code"),
+    ("rs", "text/x-rust", "code"),
+    ("go", "text/x-go", "code"),
+    ("php", "application/x-httpd-php", "code"),
+    ("rb", "text/x-ruby", "code"),
+    ("swift", "text/x-swift", "code"),
+    ("kt", "text/x-kotlin", "code"),
+    ("sh", "application/x-sh", "code"),
+    ("sql", "application/sql", "code"),
+    ("json", "application/json", "code"),
+    ("xml", "application/xml", "code"),
+    ("yaml", "application/yaml", "code"),
+    ("yml", "application/yaml", "code"),
+    ("toml", "application/toml", "code"),
+];
+
+// 按 MIME 前缀兜底的类别
+const PREFIX_CATEGORIES: &[(&str, &str)] = &[
+    ("image/", "image"),
+    ("video/", "video"),
+    ("audio/", "audio"),
+    ("text/", "text"),
+];
+
+// 文件头识别出的这些类型只说明了容器格式，扩展名表中有更具体的类型时以扩展名为准
+const GENERIC_MIMES: &[&str] = &[UNKNOWN_MIME, "application/zip", "application/x-ole-storage"];
+
+/// 一个扩展名对应的类型
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileTypeEntry {
+    pub mime_type: String,
+    pub category: String,
+}
+
+/// 用户自定义的类型表，保存在应用数据目录的 file_types.json 中
+///
+/// ```json
+/// {
+///   "extensions": { "fig": { "mime_type": "application/x-figma", "category": "design" } },
+///   "mime_categories": { "application/vnd.apache.parquet": "dataset" }
+/// }
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FileTypeOverrides {
+    /// 扩展名（不含点，不区分大小写）到类型的映射
+    #[serde(default)]
+    pub extensions: HashMap<String, FileTypeEntry>,
+    /// MIME 类型到类别的映射
+    #[serde(default)]
+    pub mime_categories: HashMap<String, String>,
+}
+
+/// 文件类型表：内置表加上用户自定义的覆盖项
+#[derive(Debug, Clone)]
+pub struct FileTypeRegistry {
+    extensions: HashMap<String, FileTypeEntry>,
+    mime_categories: HashMap<String, String>,
+    /// 用户为 MIME 类型指定的类别，优先于所有内置类别
+    mime_overrides: HashMap<String, String>,
+}
+
+impl FileTypeRegistry {
+    /// 只包含内置表
+    pub fn builtin() -> Self {
+        let mut extensions = HashMap::new();
+        let mut mime_categories = HashMap::new();
+
+        for (extension, mime_type, category) in BUILTIN_TYPES {
+            extensions.insert(
+                extension.to_string(),
+                FileTypeEntry {
+                    mime_type: mime_type.to_string(),
+                    category: category.to_string(),
+                },
+            );
+            mime_categories
+                .entry(mime_type.to_string())
+                .or_insert_with(|| category.to_string());
+        }
+
+        Self {
+            extensions,
+            mime_categories,
+            mime_overrides: HashMap::new(),
+        }
+    }
+
+    /// 加载内置表并应用用户覆盖项，文件不存在或无法解析时只使用内置表
+    pub fn load_or_builtin(path: &Path) -> Self {
+        let mut registry = Self::builtin();
+        if let Ok(content) = std::fs::read_to_string(path) {
+            match serde_json::from_str::<FileTypeOverrides>(&content) {
+                Ok(overrides) => registry.apply(overrides),
+                Err(e) => tracing::warn!("解析文件类型配置失败，使用内置类型表: {}", e),
+            }
+        }
+        registry
+    }
+
+    /// 应用覆盖项，同名扩展名和 MIME 类型以覆盖项为准
+    pub fn apply(&mut self, overrides: FileTypeOverrides) {
+        for (extension, entry) in overrides.extensions {
+            let extension = extension.trim_start_matches('.').to_lowercase();
+            self.extensions.insert(extension, entry);
+        }
+        self.mime_overrides.extend(overrides.mime_categories);
+    }
+
+    /// 根据扩展名和文件头识别结果确定 MIME 类型和类别
+    pub fn resolve(&self, extension: &str, detected_mime: Option<&str>) -> FileTypeEntry {
+        let entry = self.extensions.get(extension);
+
+        let mime_type = match (detected_mime, entry) {
+            (Some(detected), Some(entry)) if GENERIC_MIMES.contains(&detected) => entry.mime_type.clone(),
+            (Some(detected), _) => detected.to_string(),
+            (None, Some(entry)) => entry.mime_type.clone(),
+            (None, None) => UNKNOWN_MIME.to_string(),
+        };
+
+        let category = self.category_for(&mime_type, entry);
+        FileTypeEntry { mime_type, category }
+    }
+
+    // 用户为 MIME 类型指定的类别最优先；其次扩展名与内容一致时使用扩展名的类别
+    // （用户可以为某个扩展名指定专门的类别），不一致时以内容为准
+    fn category_for(&self, mime_type: &str, entry: Option<&FileTypeEntry>) -> String {
+        if let Some(category) = self.mime_overrides.get(mime_type) {
+            return category.clone();
+        }
+        if let Some(entry) = entry.filter(|entry| entry.mime_type == mime_type) {
+            return entry.category.clone();
+        }
+        if let Some(category) = self.mime_categories.get(mime_type) {
+            return category.clone();
+        }
+        if let Some((_, category)) = PREFIX_CATEGORIES
+            .iter()
+            .find(|(prefix, _)| mime_type.starts_with(prefix))
+        {
+            return category.to_string();
+        }
+        entry
+            .map(|entry| entry.category.clone())
+            .unwrap_or_else(|| UNKNOWN_CATEGORY.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn overrides(json: &str) -> FileTypeOverrides {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn mime_override_replaces_builtin_category() {
+        let mut registry = FileTypeRegistry::builtin();
+        assert_eq!(registry.resolve("pdf", Some("application/pdf")).category, "document");
+
+        registry.apply(overrides(r#"{ "mime_categories": { "application/pdf": "book" } }"#));
+        assert_eq!(registry.resolve("pdf", Some("application/pdf")).category, "book");
+        assert_eq!(registry.resolve("pdf", None).category, "book");
+        // 扩展名不同但内容相同的文件也使用覆盖后的类别
+        assert_eq!(registry.resolve("bin", Some("application/pdf")).category, "book");
+    }
+
+    #[test]
+    fn extension_override_applies_when_content_matches() {
+        let mut registry = FileTypeRegistry::builtin();
+        registry.apply(overrides(
+            r#"{ "extensions": { ".FIG": { "mime_type": "application/x-figma", "category": "design" } } }"#,
+        ));
+
+        let entry = registry.resolve("fig", None);
+        assert_eq!(entry.mime_type, "application/x-figma");
+        assert_eq!(entry.category, "design");
+        // 内容与扩展名不一致时以内容为准
+        assert_eq!(registry.resolve("fig", Some("image/png")).category, "image");
+    }
+}
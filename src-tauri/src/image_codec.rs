@@ -0,0 +1,189 @@
+use base64::{engine::general_purpose, Engine as _};
+use clipboard_rs::common::RustImage;
+use clipboard_rs::RustImageData;
+use image::{DynamicImage, ImageFormat};
+
+/// 缩略图最大边长
+const THUMBNAIL_SIZE: u32 = 256;
+
+/// 从剪贴板读取并编码后的图片
+pub struct CapturedImage {
+    pub png: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+    /// base64 编码的 PNG 缩略图
+    pub thumbnail: String,
+}
+
+/// 将剪贴板图片编码为 PNG，并生成缩略图
+pub fn encode_captured_image(image: &RustImageData) -> Result<CapturedImage, String> {
+    let (width, height) = image.get_size();
+    if width == 0 || height == 0 {
+        return Err("图片为空".to_string());
+    }
+
+    let png = image
+        .to_png()
+        .map_err(|e| format!("图片编码失败: {}", e))?
+        .get_bytes()
+        .to_vec();
+
+    let thumbnail_png = image
+        .thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+        .and_then(|thumbnail| thumbnail.to_png())
+        .map_err(|e| format!("生成缩略图失败: {}", e))?;
+
+    Ok(CapturedImage {
+        png,
+        width,
+        height,
+        thumbnail: general_purpose::STANDARD.encode(thumbnail_png.get_bytes()),
+    })
+}
+
+/// 支持写回剪贴板的图片格式
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SourceFormat {
+    Png,
+    Jpeg,
+    WebP,
+    Bmp,
+}
+
+impl SourceFormat {
+    fn image_format(self) -> ImageFormat {
+        match self {
+            SourceFormat::Png => ImageFormat::Png,
+            SourceFormat::Jpeg => ImageFormat::Jpeg,
+            SourceFormat::WebP => ImageFormat::WebP,
+            SourceFormat::Bmp => ImageFormat::Bmp,
+        }
+    }
+}
+
+/// 通过文件头识别图片格式
+pub fn detect_format(bytes: &[u8]) -> Result<SourceFormat, String> {
+    if bytes.is_empty() {
+        return Err("图片数据为空".to_string());
+    }
+
+    let kind = infer::get(bytes).ok_or_else(|| "无法识别的图片格式".to_string())?;
+    match kind.mime_type() {
+        "image/png" => Ok(SourceFormat::Png),
+        "image/jpeg" => Ok(SourceFormat::Jpeg),
+        "image/webp" => Ok(SourceFormat::WebP),
+        "image/bmp" => Ok(SourceFormat::Bmp),
+        other => Err(format!("不支持的图片格式: {}", other)),
+    }
+}
+
+/// 解码图片数据，统一转换为 RGBA8
+///
+/// 各平台剪贴板接受的格式不同（X11 为 PNG，Windows 为 DIB，macOS 为 TIFF/PNG），
+/// clipboard-rs 写入时会从 RGBA 像素重新编码，这里只需保证像素格式统一。
+pub fn decode_image(bytes: &[u8]) -> Result<DynamicImage, String> {
+    let format = detect_format(bytes)?;
+    let image = image::load_from_memory_with_format(bytes, format.image_format())
+        .map_err(|e| format!("图片解码失败: {}", e))?;
+
+    if image.width() == 0 || image.height() == 0 {
+        return Err("图片尺寸为空".to_string());
+    }
+
+    Ok(match image {
+        DynamicImage::ImageRgba8(_) => image,
+        other => DynamicImage::ImageRgba8(other.to_rgba8()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage, Rgba, RgbaImage};
+    use std::io::Cursor;
+
+    // 每个像素的四个通道都不同，红蓝通道颠倒时能被发现
+    fn sample_rgba(width: u32, height: u32) -> RgbaImage {
+        RgbaImage::from_fn(width, height, |x, y| {
+            Rgba([(x * 40) as u8, (y * 60) as u8, 200, 255 - (x + y) as u8])
+        })
+    }
+
+    fn encode(image: DynamicImage, format: ImageFormat) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        image.write_to(&mut Cursor::new(&mut bytes), format).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn png_round_trip_keeps_pixels() {
+        let original = sample_rgba(5, 3);
+        let bytes = encode(DynamicImage::ImageRgba8(original.clone()), ImageFormat::Png);
+
+        assert_eq!(detect_format(&bytes), Ok(SourceFormat::Png));
+        let decoded = decode_image(&bytes).unwrap();
+        assert_eq!(decoded.as_rgba8(), Some(&original));
+    }
+
+    #[test]
+    fn bmp_bgra_pixels_convert_to_rgba() {
+        // BMP（Windows DIB）按 BGRA 顺序存储像素，解码后应与原 RGBA 像素一致
+        let original = sample_rgba(4, 4);
+        let bytes = encode(DynamicImage::ImageRgba8(original.clone()), ImageFormat::Bmp);
+
+        assert_eq!(detect_format(&bytes), Ok(SourceFormat::Bmp));
+        let decoded = decode_image(&bytes).unwrap();
+        assert_eq!(decoded.as_rgba8(), Some(&original));
+
+        // 再编码回 BMP 得到相同的数据
+        assert_eq!(encode(decoded, ImageFormat::Bmp), bytes);
+    }
+
+    #[test]
+    fn rgb_images_are_converted_to_rgba() {
+        let original = RgbImage::from_pixel(3, 2, Rgb([10, 20, 30]));
+        let bytes = encode(DynamicImage::ImageRgb8(original), ImageFormat::Png);
+
+        let decoded = decode_image(&bytes).unwrap();
+        let rgba = decoded.as_rgba8().expect("应转换为 RGBA8");
+        assert!(rgba.pixels().all(|pixel| *pixel == Rgba([10, 20, 30, 255])));
+    }
+
+    #[test]
+    fn jpeg_is_decoded_to_rgba() {
+        let original = RgbImage::from_pixel(8, 8, Rgb([120, 120, 120]));
+        let bytes = encode(DynamicImage::ImageRgb8(original), ImageFormat::Jpeg);
+
+        assert_eq!(detect_format(&bytes), Ok(SourceFormat::Jpeg));
+        let decoded = decode_image(&bytes).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (8, 8));
+        assert!(decoded.as_rgba8().is_some());
+    }
+
+    #[test]
+    fn invalid_data_is_rejected() {
+        assert!(decode_image(&[]).is_err());
+        assert!(decode_image(b"not an image").is_err());
+
+        // 文件头是 PNG，但内容被截断
+        let bytes = encode(DynamicImage::ImageRgba8(sample_rgba(5, 3)), ImageFormat::Png);
+        assert!(decode_image(&bytes[..bytes.len() / 2]).is_err());
+
+        // 能识别但不支持的格式
+        let gif = b"GIF89a\x01\x00\x01\x00\x00\x00\x00;";
+        assert!(detect_format(gif).unwrap_err().contains("image/gif"));
+    }
+
+    #[test]
+    fn captured_image_is_encoded_as_png() {
+        let original = sample_rgba(300, 10);
+        let data = RustImageData::from_dynamic_image(DynamicImage::ImageRgba8(original.clone()));
+
+        let captured = encode_captured_image(&data).unwrap();
+        assert_eq!((captured.width, captured.height), (300, 10));
+        assert_eq!(decode_image(&captured.png).unwrap().as_rgba8(), Some(&original));
+
+        let thumbnail = general_purpose::STANDARD.decode(&captured.thumbnail).unwrap();
+        assert!(decode_image(&thumbnail).unwrap().width() <= THUMBNAIL_SIZE);
+    }
+}
@@ -0,0 +1,46 @@
+use std::fs::{File, OpenOptions, TryLockError};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// 锁文件名，位于应用数据目录下
+const LOCK_FILE: &str = "clippy.lock";
+
+/// 应用数据目录的独占锁
+///
+/// GUI 在运行期间一直持有该锁；命令行工具修改历史前也需要获取，
+/// 保证同一时间只有一个进程写入存储文件。锁随对象释放。
+pub struct InstanceLock {
+    _file: File,
+}
+
+impl InstanceLock {
+    /// 尝试获取锁，已被其他进程持有时返回 None
+    pub fn try_acquire(data_dir: &Path) -> std::io::Result<Option<Self>> {
+        std::fs::create_dir_all(data_dir)?;
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(data_dir.join(LOCK_FILE))?;
+
+        match file.try_lock() {
+            Ok(()) => Ok(Some(Self { _file: file })),
+            Err(TryLockError::WouldBlock) => Ok(None),
+            Err(TryLockError::Error(e)) => Err(e),
+        }
+    }
+
+    /// 在 timeout 内反复尝试获取锁
+    pub fn acquire_with_timeout(data_dir: &Path, timeout: Duration) -> std::io::Result<Option<Self>> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(lock) = Self::try_acquire(data_dir)? {
+                return Ok(Some(lock));
+            }
+            if Instant::now() >= deadline {
+                return Ok(None);
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+    }
+}
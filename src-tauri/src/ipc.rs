@@ -0,0 +1,527 @@
+//! 本地自动化接口：应用数据目录下的 Unix 套接字
+//!
+//! 协议为按行分隔的 JSON-RPC 2.0。连接后第一个请求必须是 `auth`，参数中的 token
+//! 与应用数据目录下 ipc_token 文件的内容一致；该文件每次启动重新生成，只有当前用户可读。
+//!
+//! ```text
+//! → {"jsonrpc":"2.0","id":1,"method":"auth","params":{"token":"..."}}
+//! ← {"jsonrpc":"2.0","id":1,"result":true}
+//! → {"jsonrpc":"2.0","id":2,"method":"subscribe","params":{"events":["clipboard-update"]}}
+//! ← {"jsonrpc":"2.0","id":2,"result":["clipboard-update"]}
+//! ← {"jsonrpc":"2.0","method":"clipboard-update","params":{...}}
+//! ```
+//!
+//! 服务端不依赖 Tauri，具体方法由 [`IpcHandler`] 实现，事件通过 [`IpcServer::events`] 广播。
+
+use anyhow::{anyhow, Context};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::io::Write;
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::net::unix::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::{broadcast, mpsc};
+
+/// 套接字文件名，位于应用数据目录下
+pub const SOCKET_FILE: &str = "clippy.sock";
+/// 认证令牌文件名，位于应用数据目录下
+pub const TOKEN_FILE: &str = "ipc_token";
+
+// 订阅者处理不及时时最多缓存的事件数
+const EVENT_CAPACITY: usize = 64;
+
+// JSON-RPC 错误码
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+pub const APP_ERROR: i64 = -32000;
+pub const UNAUTHORIZED: i64 = -32001;
+
+/// 请求处理失败时返回给客户端的错误
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        Self { code, message: message.into() }
+    }
+
+    pub fn method_not_found(method: &str) -> Self {
+        Self::new(METHOD_NOT_FOUND, format!("未知方法: {}", method))
+    }
+
+    pub fn invalid_params(e: impl std::fmt::Display) -> Self {
+        Self::new(INVALID_PARAMS, format!("无效的参数: {}", e))
+    }
+}
+
+impl From<String> for RpcError {
+    fn from(message: String) -> Self {
+        Self::new(APP_ERROR, message)
+    }
+}
+
+impl std::fmt::Display for RpcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.message, self.code)
+    }
+}
+
+impl std::error::Error for RpcError {}
+
+/// 把请求参数解析为方法需要的类型，缺少参数时按 null 处理
+pub fn parse_params<T: for<'de> Deserialize<'de>>(params: Value) -> Result<T, RpcError> {
+    serde_json::from_value(params).map_err(RpcError::invalid_params)
+}
+
+/// 处理认证之后的请求；auth、subscribe 和 unsubscribe 由服务端自己处理
+pub trait IpcHandler: Send + Sync + 'static {
+    fn call(&self, method: &str, params: Value) -> impl Future<Output = Result<Value, RpcError>> + Send;
+}
+
+/// 推送给订阅者的事件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IpcEvent {
+    pub event: String,
+    pub payload: Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct Request {
+    #[serde(default)]
+    id: Value,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Deserialize)]
+struct AuthParams {
+    token: String,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct SubscribeParams {
+    /// 为空时订阅所有事件
+    events: Vec<String>,
+}
+
+fn response(id: Value, result: Result<Value, RpcError>) -> Value {
+    match result {
+        Ok(result) => serde_json::json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(error) => serde_json::json!({ "jsonrpc": "2.0", "id": id, "error": error }),
+    }
+}
+
+fn notification(event: &IpcEvent) -> Value {
+    serde_json::json!({ "jsonrpc": "2.0", "method": event.event, "params": event.payload })
+}
+
+/// 监听中的自动化接口
+pub struct IpcServer<H> {
+    listener: UnixListener,
+    socket_path: PathBuf,
+    token: Arc<String>,
+    handler: Arc<H>,
+    events: broadcast::Sender<IpcEvent>,
+}
+
+impl<H: IpcHandler> IpcServer<H> {
+    /// 在 data_dir 下创建套接字并生成新的认证令牌
+    ///
+    /// 调用方需要保证同一目录只有一个服务端（GUI 持有应用数据目录的锁），
+    /// 因此残留的套接字文件直接删除。
+    pub fn bind(data_dir: &Path, handler: H) -> anyhow::Result<Self> {
+        std::fs::create_dir_all(data_dir)?;
+        let token = uuid::Uuid::new_v4().simple().to_string();
+        write_private_file(&data_dir.join(TOKEN_FILE), &token).context("Failed to write ipc token")?;
+
+        let socket_path = data_dir.join(SOCKET_FILE);
+        match std::fs::remove_file(&socket_path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e).context("Failed to remove stale socket"),
+        }
+        let listener = UnixListener::bind(&socket_path).context("Failed to bind ipc socket")?;
+        std::fs::set_permissions(&socket_path, std::fs::Permissions::from_mode(0o600))?;
+
+        let (events, _) = broadcast::channel(EVENT_CAPACITY);
+        Ok(Self {
+            listener,
+            socket_path,
+            token: Arc::new(token),
+            handler: Arc::new(handler),
+            events,
+        })
+    }
+
+    /// 发送事件的通道，所有订阅了该事件的连接都会收到
+    pub fn events(&self) -> broadcast::Sender<IpcEvent> {
+        self.events.clone()
+    }
+
+    pub fn socket_path(&self) -> &Path {
+        &self.socket_path
+    }
+
+    /// 接受连接直到监听失败，每个连接在单独的任务中处理
+    pub async fn serve(self) -> std::io::Result<()> {
+        loop {
+            let (stream, _) = self.listener.accept().await?;
+            let connection = Connection {
+                handler: self.handler.clone(),
+                token: self.token.clone(),
+                events: self.events.clone(),
+            };
+            tokio::spawn(async move {
+                if let Err(e) = connection.run(stream).await {
+                    tracing::debug!("IPC 连接结束: {}", e);
+                }
+            });
+        }
+    }
+}
+
+impl<H> Drop for IpcServer<H> {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.socket_path);
+    }
+}
+
+struct Connection<H> {
+    handler: Arc<H>,
+    token: Arc<String>,
+    events: broadcast::Sender<IpcEvent>,
+}
+
+impl<H: IpcHandler> Connection<H> {
+    async fn run(self, stream: UnixStream) -> std::io::Result<()> {
+        let (reader, writer) = stream.into_split();
+
+        // 响应和事件通知都经过同一个写入任务，避免交错写入半行
+        let (sender, receiver) = mpsc::unbounded_channel::<Value>();
+        let writer_task = tokio::spawn(write_messages(writer, receiver));
+
+        let mut lines = BufReader::new(reader).lines();
+        let mut authenticated = false;
+        let mut subscription: Option<tokio::task::JoinHandle<()>> = None;
+
+        while let Some(line) = lines.next_line().await? {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let request: Request = match serde_json::from_str(&line) {
+                Ok(request) => request,
+                Err(e) => {
+                    let code = if serde_json::from_str::<Value>(&line).is_ok() { INVALID_REQUEST } else { PARSE_ERROR };
+                    let _ = sender.send(response(Value::Null, Err(RpcError::new(code, e.to_string()))));
+                    continue;
+                }
+            };
+
+            let result = match request.method.as_str() {
+                "auth" => parse_params::<AuthParams>(request.params).and_then(|params| {
+                    authenticated = params.token == *self.token;
+                    if authenticated {
+                        Ok(Value::Bool(true))
+                    } else {
+                        Err(RpcError::new(UNAUTHORIZED, "认证失败"))
+                    }
+                }),
+                _ if !authenticated => Err(RpcError::new(UNAUTHORIZED, "请先调用 auth 认证")),
+                "subscribe" => parse_params::<Option<SubscribeParams>>(request.params).map(|params| {
+                    let events = params.unwrap_or_default().events;
+                    if let Some(previous) = subscription.take() {
+                        previous.abort();
+                    }
+                    subscription = Some(tokio::spawn(forward_events(
+                        self.events.subscribe(),
+                        events.clone(),
+                        sender.clone(),
+                    )));
+                    serde_json::json!(events)
+                }),
+                "unsubscribe" => {
+                    if let Some(previous) = subscription.take() {
+                        previous.abort();
+                    }
+                    Ok(Value::Bool(true))
+                }
+                method => self.handler.call(method, request.params).await,
+            };
+            let _ = sender.send(response(request.id, result));
+        }
+
+        if let Some(subscription) = subscription {
+            subscription.abort();
+        }
+        drop(sender);
+        let _ = writer_task.await;
+        Ok(())
+    }
+}
+
+async fn write_messages(mut writer: OwnedWriteHalf, mut receiver: mpsc::UnboundedReceiver<Value>) {
+    while let Some(message) = receiver.recv().await {
+        let mut line = message.to_string();
+        line.push('\n');
+        if writer.write_all(line.as_bytes()).await.is_err() {
+            break;
+        }
+    }
+}
+
+async fn forward_events(
+    mut receiver: broadcast::Receiver<IpcEvent>,
+    events: Vec<String>,
+    sender: mpsc::UnboundedSender<Value>,
+) {
+    loop {
+        match receiver.recv().await {
+            Ok(event) => {
+                if !events.is_empty() && !events.contains(&event.event) {
+                    continue;
+                }
+                if sender.send(notification(&event)).is_err() {
+                    break;
+                }
+            }
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                tracing::warn!("IPC 订阅者处理过慢，跳过 {} 个事件", skipped);
+            }
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+    }
+}
+
+// 只有当前用户可读写的文件，先写临时文件再重命名
+fn write_private_file(path: &Path, content: &str) -> std::io::Result<()> {
+    let temp_path = path.with_extension("tmp");
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .truncate(true)
+        .write(true)
+        .mode(0o600)
+        .open(&temp_path)?;
+    file.write_all(content.as_bytes())?;
+    std::fs::set_permissions(&temp_path, std::fs::Permissions::from_mode(0o600))?;
+    std::fs::rename(&temp_path, path)
+}
+
+/// 自动化接口的客户端，连接后自动完成认证
+pub struct IpcClient {
+    lines: Lines<BufReader<OwnedReadHalf>>,
+    writer: OwnedWriteHalf,
+    next_id: u64,
+    // 等待响应期间收到的事件
+    pending_events: VecDeque<IpcEvent>,
+}
+
+impl IpcClient {
+    /// 连接 data_dir 下正在运行的服务端
+    pub async fn connect(data_dir: &Path) -> anyhow::Result<Self> {
+        let token = std::fs::read_to_string(data_dir.join(TOKEN_FILE)).context("Failed to read ipc token")?;
+        let stream = UnixStream::connect(data_dir.join(SOCKET_FILE))
+            .await
+            .context("Failed to connect to ipc socket")?;
+        let (reader, writer) = stream.into_split();
+
+        let mut client = Self {
+            lines: BufReader::new(reader).lines(),
+            writer,
+            next_id: 1,
+            pending_events: VecDeque::new(),
+        };
+        client.call("auth", serde_json::json!({ "token": token.trim() })).await?;
+        Ok(client)
+    }
+
+    /// 调用方法并等待结果；服务端返回错误时为 RpcError
+    pub async fn call(&mut self, method: &str, params: Value) -> anyhow::Result<Value> {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let mut line = serde_json::json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }).to_string();
+        line.push('\n');
+        self.writer.write_all(line.as_bytes()).await?;
+
+        loop {
+            let message = self.read_message().await?;
+            if message.get("id").and_then(Value::as_u64) != Some(id) {
+                self.queue_event(message);
+                continue;
+            }
+            if let Some(error) = message.get("error") {
+                let error: RpcError = serde_json::from_value(error.clone())?;
+                return Err(error.into());
+            }
+            return Ok(message.get("result").cloned().unwrap_or(Value::Null));
+        }
+    }
+
+    /// 订阅事件，events 为空时订阅所有事件
+    pub async fn subscribe(&mut self, events: &[&str]) -> anyhow::Result<()> {
+        self.call("subscribe", serde_json::json!({ "events": events })).await?;
+        Ok(())
+    }
+
+    /// 等待下一个订阅的事件
+    pub async fn next_event(&mut self) -> anyhow::Result<IpcEvent> {
+        loop {
+            if let Some(event) = self.pending_events.pop_front() {
+                return Ok(event);
+            }
+            let message = self.read_message().await?;
+            self.queue_event(message);
+        }
+    }
+
+    async fn read_message(&mut self) -> anyhow::Result<Value> {
+        let line = self.lines.next_line().await?.ok_or_else(|| anyhow!("连接已关闭"))?;
+        Ok(serde_json::from_str(&line)?)
+    }
+
+    // 没有 id 的消息是事件通知，其余是不属于当前请求的响应，忽略
+    fn queue_event(&mut self, message: Value) {
+        if message.get("id").is_some() {
+            return;
+        }
+        if let (Some(event), Some(payload)) = (message.get("method").and_then(Value::as_str), message.get("params")) {
+            self.pending_events.push_back(IpcEvent {
+                event: event.to_string(),
+                payload: payload.clone(),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    // 只实现 list 和 copy 的模拟处理器，记录被复制的项目
+    #[derive(Default)]
+    struct FakeHandler {
+        copied: Mutex<Vec<String>>,
+    }
+
+    #[derive(Deserialize)]
+    struct CopyParams {
+        id: String,
+    }
+
+    impl IpcHandler for Arc<FakeHandler> {
+        async fn call(&self, method: &str, params: Value) -> Result<Value, RpcError> {
+            match method {
+                "list" => Ok(serde_json::json!([{ "id": "a", "content": "hello" }, { "id": "b", "content": "world" }])),
+                "copy" => {
+                    let params: CopyParams = parse_params(params)?;
+                    if params.id != "a" && params.id != "b" {
+                        return Err(format!("项目不存在: {}", params.id).into());
+                    }
+                    self.copied.lock().unwrap().push(params.id);
+                    Ok(Value::Bool(true))
+                }
+                other => Err(RpcError::method_not_found(other)),
+            }
+        }
+    }
+
+    fn rpc_code(error: anyhow::Error) -> i64 {
+        error.downcast_ref::<RpcError>().expect("应为 RpcError").code
+    }
+
+    // 不经过 IpcClient，直接按行发送请求
+    async fn raw_call(lines: &mut Lines<BufReader<OwnedReadHalf>>, writer: &mut OwnedWriteHalf, request: Value) -> Value {
+        let mut line = request.to_string();
+        line.push('\n');
+        writer.write_all(line.as_bytes()).await.unwrap();
+        serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap()
+    }
+
+    fn runtime() -> tokio::runtime::Runtime {
+        tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap()
+    }
+
+    #[test]
+    fn rejects_requests_without_valid_token() {
+        let dir = tempfile::tempdir().unwrap();
+        runtime().block_on(async {
+            let server = IpcServer::bind(dir.path(), Arc::new(FakeHandler::default())).unwrap();
+            tokio::spawn(server.serve());
+
+            let mode = std::fs::metadata(dir.path().join(TOKEN_FILE)).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+
+            let stream = UnixStream::connect(dir.path().join(SOCKET_FILE)).await.unwrap();
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = BufReader::new(reader).lines();
+
+            let reply = raw_call(&mut lines, &mut writer, serde_json::json!({ "jsonrpc": "2.0", "id": 1, "method": "list" })).await;
+            assert_eq!(reply["error"]["code"], UNAUTHORIZED);
+
+            let auth = serde_json::json!({ "jsonrpc": "2.0", "id": 2, "method": "auth", "params": { "token": "wrong" } });
+            let reply = raw_call(&mut lines, &mut writer, auth).await;
+            assert_eq!(reply["error"]["code"], UNAUTHORIZED);
+
+            // 认证失败后仍然不能调用方法
+            let reply = raw_call(&mut lines, &mut writer, serde_json::json!({ "jsonrpc": "2.0", "id": 3, "method": "copy", "params": { "id": "a" } })).await;
+            assert_eq!(reply["id"], 3);
+            assert_eq!(reply["error"]["code"], UNAUTHORIZED);
+
+            let reply = raw_call(&mut lines, &mut writer, Value::String("not a request".to_string())).await;
+            assert_eq!(reply["error"]["code"], INVALID_REQUEST);
+        });
+    }
+
+    #[test]
+    fn round_trips_list_and_copy() {
+        let dir = tempfile::tempdir().unwrap();
+        let handler = Arc::new(FakeHandler::default());
+        runtime().block_on(async {
+            let server = IpcServer::bind(dir.path(), handler.clone()).unwrap();
+            tokio::spawn(server.serve());
+
+            let mut client = IpcClient::connect(dir.path()).await.unwrap();
+            let items = client.call("list", Value::Null).await.unwrap();
+            assert_eq!(items[1]["content"], "world");
+
+            assert_eq!(client.call("copy", serde_json::json!({ "id": "b" })).await.unwrap(), Value::Bool(true));
+            assert_eq!(rpc_code(client.call("copy", serde_json::json!({ "id": "missing" })).await.unwrap_err()), APP_ERROR);
+            assert_eq!(rpc_code(client.call("copy", Value::Null).await.unwrap_err()), INVALID_PARAMS);
+            assert_eq!(rpc_code(client.call("paste", Value::Null).await.unwrap_err()), METHOD_NOT_FOUND);
+        });
+        assert_eq!(*handler.copied.lock().unwrap(), vec!["b".to_string()]);
+    }
+
+    #[test]
+    fn forwards_subscribed_events() {
+        let dir = tempfile::tempdir().unwrap();
+        runtime().block_on(async {
+            let server = IpcServer::bind(dir.path(), Arc::new(FakeHandler::default())).unwrap();
+            let events = server.events();
+            tokio::spawn(server.serve());
+
+            let mut client = IpcClient::connect(dir.path()).await.unwrap();
+            client.subscribe(&["clipboard-update"]).await.unwrap();
+
+            for event in ["sync-error", "clipboard-update"] {
+                events.send(IpcEvent { event: event.to_string(), payload: serde_json::json!({ "id": "a" }) }).unwrap();
+            }
+            let event = client.next_event().await.unwrap();
+            assert_eq!(event.event, "clipboard-update");
+            assert_eq!(event.payload["id"], "a");
+        });
+    }
+}
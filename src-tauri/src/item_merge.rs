@@ -0,0 +1,102 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::file_detect;
+use crate::file_types::FileTypeRegistry;
+use crate::storage::{ClipboardItem, FileTypeInfo};
+use crate::text_classify;
+
+/// 合并多个历史项目，生成一个新项目（尚未保存）
+///
+/// 文本项目按顺序用 separator 连接；文件项目合并文件列表并去重，重新检测文件类型和大小。
+/// 只能合并同类型的项目，图片不支持合并。
+pub fn merge_items(
+    items: &[ClipboardItem],
+    separator: &str,
+    registry: &FileTypeRegistry,
+    timestamp: u64,
+) -> Result<ClipboardItem, String> {
+    if items.len() < 2 {
+        return Err("至少需要两个项目才能合并".to_string());
+    }
+
+    let item_type = items[0].item_type.as_str();
+    if items.iter().any(|item| item.item_type != item_type) {
+        return Err("只能合并同类型的项目".to_string());
+    }
+
+    match item_type {
+        "text" => {
+            let content = items
+                .iter()
+                .map(|item| item.content.as_str())
+                .collect::<Vec<_>>()
+                .join(separator);
+            Ok(ClipboardItem {
+                id: uuid::Uuid::new_v4().to_string(),
+                size: Some(content.len() as u64),
+                detected_kind: text_classify::classify(&content),
+                content,
+                timestamp,
+                item_type: "text".to_string(),
+                ..Default::default()
+            })
+        }
+        "files" => {
+            let mut seen = HashSet::new();
+            let file_paths: Vec<String> = items
+                .iter()
+                .flat_map(|item| item.file_paths.iter().flatten())
+                .filter(|path| seen.insert(path.as_str()))
+                .cloned()
+                .collect();
+            let previous: Vec<&FileTypeInfo> = items
+                .iter()
+                .flat_map(|item| item.file_types.iter().flatten())
+                .collect();
+
+            let file_types = merge_file_types(&file_paths, &previous, registry);
+            Ok(ClipboardItem {
+                id: uuid::Uuid::new_v4().to_string(),
+                content: format!("{} 个文件", file_paths.len()),
+                timestamp,
+                item_type: "files".to_string(),
+                size: Some(file_types.iter().map(|info| info.size).sum()),
+                file_paths: Some(file_paths),
+                file_types: Some(file_types),
+                ..Default::default()
+            })
+        }
+        other => Err(format!("不支持合并该类型的项目: {}", other)),
+    }
+}
+
+// 仍然存在的文件重新检测，已不存在的沿用原来的信息；原来保留的副本继续引用
+fn merge_file_types(
+    file_paths: &[String],
+    previous: &[&FileTypeInfo],
+    registry: &FileTypeRegistry,
+) -> Vec<FileTypeInfo> {
+    let find_previous = |path: &str| previous.iter().find(|info| info.path == path);
+
+    let existing: Vec<String> = file_paths
+        .iter()
+        .filter(|path| Path::new(path).exists() || find_previous(path).is_none())
+        .cloned()
+        .collect();
+    let mut detected = file_detect::detect_file_types(&existing, registry).into_iter();
+
+    file_paths
+        .iter()
+        .map(|path| {
+            let old = find_previous(path);
+            if existing.contains(path) {
+                let mut info = detected.next().unwrap_or_default();
+                info.snapshot = old.and_then(|old| old.snapshot.clone());
+                info
+            } else {
+                old.map(|old| (*old).clone()).unwrap_or_default()
+            }
+        })
+        .collect()
+}
@@ -1,44 +1,140 @@
 use clipboard_rs::{
-    Clipboard, ClipboardContext, ClipboardHandler, ClipboardWatcher, 
-    ClipboardWatcherContext, ContentFormat
+    Clipboard, ClipboardContent, ClipboardContext, ClipboardHandler, ClipboardWatcher, 
+    ClipboardWatcherContext, ContentFormat, RustImageData, WatcherShutdown
 };
+use clipboard_rs::common::RustImage;
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::path::{Path, PathBuf};
 use tauri::{AppHandle, Emitter, Manager};
-use infer;
-use tauri::tray::{TrayIconBuilder, TrayIconEvent};
+use tauri::menu::{Menu, MenuItem};
+use tauri::tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent};
 use base64::{Engine as _, engine::general_purpose};
 
 
 // 导入存储模块
 mod storage;
-use storage::{StorageEngine, StorageStats, ClipboardItem, FileTypeInfo};
+mod blob_store;
+use blob_store::BlobStore;
+mod text_classify;
+mod text_transform;
+mod snippets;
+use snippets::{RenderContext, RenderedSnippet, Snippet, SnippetStore};
+use text_transform::{Transform, TransformChain, TransformOutput};
+mod file_detect;
+mod file_types;
+mod file_snapshot;
+mod item_merge;
+mod rules;
+use rules::RuleEngine;
+use file_types::FileTypeRegistry;
+use storage::{StorageEngine, StorageStats, ClipboardItem, FileTypeInfo, ImageInfo};
+
+// 导入图片编解码模块
+mod image_codec;
+
+// 导入自身写入检测模块
+mod loopback;
+use loopback::WriteRegistry;
 
 // 导入同步模块
 mod sync;
+mod sync_clock;
+mod sync_outbox;
 mod storage_adapter;
-use sync::{SyncEngine, SyncConfig, SyncClipboardItem};
+use sync::{SyncEngine, SyncConfig, SyncClipboardItem, SyncDelta};
 use storage_adapter::{StorageConfig};
 
+// 导入设置和来源应用模块
+mod settings;
+mod source_app;
+use settings::{AppSettings, FileSnapshotSettings};
+use source_app::{AppFilter, SystemWindowInfoProvider, WindowInfo, WindowInfoProvider};
+
+// 导入记录状态模块
+mod capture_state;
+mod instance_lock;
+use instance_lock::InstanceLock;
+
+pub mod cli;
+mod paste_queue;
+#[cfg(unix)]
+pub mod ipc;
+use paste_queue::{PasteQueue, PasteQueueStatus, QueueOrder};
+use capture_state::{CaptureState, CaptureStatus};
+
 // 全局状态
 type ClipboardStorage = Arc<Mutex<StorageEngine>>;
 type ClipboardSync = Arc<SyncEngine>;
 type ClipboardSyncContainer = Arc<Mutex<Option<ClipboardSync>>>;
+type SharedSettings = Arc<Mutex<AppSettings>>;
+type ClipboardWrites = Arc<WriteRegistry>;
+type SharedCaptureState = Arc<Mutex<CaptureState>>;
+type SharedMonitor = Arc<Mutex<ClipboardMonitor>>;
+type SharedSnippets = Arc<Mutex<SnippetStore>>;
+type SharedPasteQueue = Arc<Mutex<PasteQueue>>;
+type SharedRules = Arc<Mutex<RuleEngine>>;
+
+/// 历史查询条件，界面、命令行工具和自动化接口共用；所有条件都为空时返回全部项目
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(default)]
+struct HistoryQuery {
+    /// 项目类型：text、files 或 image
+    item_type: Option<String>,
+    /// 识别类型名称，如 "url"、"code"
+    kind: Option<String>,
+    /// 内容或文件路径包含的关键字，不区分大小写
+    search: Option<String>,
+    limit: Option<usize>,
+}
+
+impl HistoryQuery {
+    fn matches(&self, item: &ClipboardItem) -> bool {
+        let search = self.search.as_ref().map(|search| search.to_lowercase());
+        self.item_type.as_ref().is_none_or(|item_type| item.item_type == *item_type)
+            && self.kind.as_ref().is_none_or(|kind| item.detected_kind.as_ref().is_some_and(|detected| detected.name() == kind))
+            && search.is_none_or(|search| {
+                item.content.to_lowercase().contains(&search)
+                    || item.file_paths.iter().flatten().any(|path| path.to_lowercase().contains(&search))
+            })
+    }
+
+    // 历史按时间倒序排列，取前 limit 个匹配的项目
+    fn run(&self, storage: &StorageEngine) -> Vec<ClipboardItem> {
+        storage
+            .get_all()
+            .into_iter()
+            .filter(|item| self.matches(item))
+            .take(self.limit.unwrap_or(usize::MAX))
+            .collect()
+    }
+}
 
+// kind 为识别类型名称（如 "url"、"code"）时只返回该类型的文本项目
 #[tauri::command]
-fn get_clipboard_history(state: tauri::State<ClipboardStorage>) -> Vec<ClipboardItem> {
-    state.lock().unwrap().get_all()
+fn get_clipboard_history(kind: Option<String>, state: tauri::State<ClipboardStorage>) -> Vec<ClipboardItem> {
+    let query = HistoryQuery { kind, ..Default::default() };
+    query.run(&state.lock().unwrap())
 }
 
 #[tauri::command]
-fn clear_clipboard_history(state: tauri::State<ClipboardStorage>) -> Result<(), String> {
-    state.lock().unwrap().clear_all().map_err(|e| e.to_string())
+fn clear_clipboard_history(app_handle: AppHandle, state: tauri::State<ClipboardStorage>) -> Result<(), String> {
+    let removed = state.lock().unwrap().clear_all().map_err(|e| e.to_string())?;
+    sync_local_deletes(&app_handle, removed);
+    Ok(())
 }
 
 #[tauri::command]
-fn delete_clipboard_item(item_id: String, state: tauri::State<ClipboardStorage>) -> Result<(), String> {
-    state.lock().unwrap().delete(&item_id).map_err(|e| e.to_string())
+fn delete_clipboard_item(app_handle: AppHandle, item_id: String, state: tauri::State<ClipboardStorage>) -> Result<(), String> {
+    let removed = {
+        let mut storage = state.lock().unwrap();
+        let item = storage.get(&item_id);
+        storage.delete(&item_id).map_err(|e| e.to_string())?;
+        item
+    };
+    sync_local_deletes(&app_handle, removed.into_iter().collect());
+    Ok(())
 }
 
 #[tauri::command]
@@ -52,28 +148,382 @@ fn compact_storage(state: tauri::State<ClipboardStorage>) -> Result<(), String>
 }
 
 #[tauri::command]
-fn copy_to_clipboard(content: String) -> Result<(), String> {
+fn get_app_filter(state: tauri::State<SharedSettings>) -> AppFilter {
+    state.lock().unwrap().app_filter.clone()
+}
+
+#[tauri::command]
+fn set_app_filter(filter: AppFilter, state: tauri::State<SharedSettings>) -> Result<(), String> {
+    let mut settings = state.lock().unwrap();
+    settings.app_filter = filter;
+    settings
+        .save_to_file(&get_app_data_dir().join("settings.json"))
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn get_file_snapshot_settings(state: tauri::State<SharedSettings>) -> FileSnapshotSettings {
+    state.lock().unwrap().file_snapshots.clone()
+}
+
+#[tauri::command]
+fn set_file_snapshot_settings(snapshots: FileSnapshotSettings, state: tauri::State<SharedSettings>) -> Result<(), String> {
+    let mut settings = state.lock().unwrap();
+    settings.file_snapshots = snapshots;
+    settings
+        .save_to_file(&get_app_data_dir().join("settings.json"))
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn get_transforms() -> Vec<Transform> {
+    Transform::ALL.to_vec()
+}
+
+#[tauri::command]
+fn get_transform_chains(state: tauri::State<SharedSettings>) -> Vec<TransformChain> {
+    state.lock().unwrap().transform_chains.clone()
+}
+
+// 保存转换链，同名的链会被替换
+#[tauri::command]
+fn save_transform_chain(chain: TransformChain, state: tauri::State<SharedSettings>) -> Result<(), String> {
+    if chain.name.trim().is_empty() {
+        return Err("转换链名称不能为空".to_string());
+    }
+    let mut settings = state.lock().unwrap();
+    match settings.transform_chains.iter_mut().find(|existing| existing.name == chain.name) {
+        Some(existing) => *existing = chain,
+        None => settings.transform_chains.push(chain),
+    }
+    settings
+        .save_to_file(&get_app_data_dir().join("settings.json"))
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn delete_transform_chain(name: String, state: tauri::State<SharedSettings>) -> Result<(), String> {
+    let mut settings = state.lock().unwrap();
+    settings.transform_chains.retain(|chain| chain.name != name);
+    settings
+        .save_to_file(&get_app_data_dir().join("settings.json"))
+        .map_err(|e| e.to_string())
+}
+
+// 转换历史项目（item_id 为空时转换当前剪贴板文本），steps 之后再执行名为 chain 的转换链
+#[tauri::command]
+fn transform_text(
+    item_id: Option<String>,
+    steps: Vec<Transform>,
+    chain: Option<String>,
+    output: TransformOutput,
+    app_handle: AppHandle,
+    state: tauri::State<ClipboardStorage>,
+    writes: tauri::State<ClipboardWrites>,
+) -> Result<String, String> {
+    let text = match item_id {
+        Some(item_id) => {
+            let item = state.lock().unwrap()
+                .get(&item_id)
+                .ok_or_else(|| format!("项目不存在: {}", item_id))?;
+            if item.item_type != "text" {
+                return Err("只能转换文本项目".to_string());
+            }
+            item.content
+        }
+        None => ClipboardContext::new()
+            .and_then(|ctx| ctx.get_text())
+            .map_err(|e| format!("读取剪贴板文本失败: {}", e))?,
+    };
+
+    let mut all_steps = steps;
+    if let Some(name) = chain {
+        let settings = app_handle.state::<SharedSettings>();
+        let settings = settings.lock().unwrap();
+        let chain = settings.transform_chains.iter()
+            .find(|chain| chain.name == name)
+            .ok_or_else(|| format!("转换链不存在: {}", name))?;
+        all_steps.extend(chain.steps.iter().copied());
+    }
+
+    let result = text_transform::apply_chain(&text, &all_steps)?;
+
+    match output {
+        TransformOutput::Preview => {}
+        TransformOutput::Copy => {
+            let ctx = ClipboardContext::new().map_err(|e| e.to_string())?;
+            writes.register(loopback::text_fingerprint(&result), None);
+            ctx.set_text(result.clone()).map_err(|e| e.to_string())?;
+        }
+        TransformOutput::Insert => {
+            let item = ClipboardItem {
+                id: uuid::Uuid::new_v4().to_string(),
+                content: result.clone(),
+                timestamp: unix_now(),
+                item_type: "text".to_string(),
+                size: Some(result.len() as u64),
+                detected_kind: text_classify::classify(&result),
+                ..Default::default()
+            };
+            HistoryRecorder::from_app(&app_handle).record(item);
+        }
+    }
+    Ok(result)
+}
+
+// 合并多个项目为一个新项目，separator 默认为换行；copy 为 true 时同时写入剪贴板
+#[tauri::command]
+async fn merge_clipboard_items(
+    item_ids: Vec<String>,
+    separator: Option<String>,
+    copy: bool,
+    app_handle: AppHandle,
+    state: tauri::State<'_, ClipboardStorage>,
+    writes: tauri::State<'_, ClipboardWrites>,
+) -> Result<ClipboardItem, String> {
+    let items = {
+        let storage = state.lock().unwrap();
+        item_ids.iter()
+            .map(|item_id| storage.get(item_id).ok_or_else(|| format!("项目不存在: {}", item_id)))
+            .collect::<Result<Vec<_>, _>>()?
+    };
+
+    // 文件类型检测可能较慢，放到阻塞线程池中执行
+    let item = tauri::async_runtime::spawn_blocking(move || {
+        let separator = separator.unwrap_or_else(|| "\n".to_string());
+        item_merge::merge_items(&items, &separator, &load_file_type_registry(), unix_now())
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    HistoryRecorder::from_app(&app_handle).record(item.clone());
+    if copy {
+        write_item_to_clipboard(&item.id, &state, &writes, false)?;
+    }
+    Ok(item)
+}
+
+// 加载文件类型表，包括用户在 file_types.json 中的自定义类型
+fn load_file_type_registry() -> FileTypeRegistry {
+    FileTypeRegistry::load_or_builtin(&get_app_data_dir().join("file_types.json"))
+}
+
+// 片段相关命令
+#[tauri::command]
+fn get_snippets(state: tauri::State<SharedSnippets>) -> Vec<Snippet> {
+    state.lock().unwrap().list()
+}
+
+// 保存片段，id 为空时新建
+#[tauri::command]
+fn save_snippet(snippet: Snippet, state: tauri::State<SharedSnippets>) -> Result<Snippet, String> {
+    state.lock().unwrap().upsert(snippet, unix_now()).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn delete_snippet(snippet_id: String, state: tauri::State<SharedSnippets>) -> Result<(), String> {
+    state.lock().unwrap().delete(&snippet_id, unix_now()).map_err(|e| e.to_string())
+}
+
+// 渲染前需要用户填写的输入项
+#[tauri::command]
+fn get_snippet_inputs(snippet_id: String, state: tauri::State<SharedSnippets>) -> Result<Vec<String>, String> {
+    let snippet = state.lock().unwrap()
+        .get(&snippet_id)
+        .ok_or_else(|| format!("片段不存在: {}", snippet_id))?;
+    Ok(snippet.inputs())
+}
+
+// 渲染片段，copy 为 true 时把结果写入剪贴板
+#[tauri::command]
+fn render_snippet(
+    snippet_id: String,
+    inputs: HashMap<String, String>,
+    copy: bool,
+    state: tauri::State<SharedSnippets>,
+    writes: tauri::State<ClipboardWrites>,
+) -> Result<RenderedSnippet, String> {
+    let snippet = state.lock().unwrap()
+        .get(&snippet_id)
+        .ok_or_else(|| format!("片段不存在: {}", snippet_id))?;
+
+    let clipboard = ClipboardContext::new().and_then(|ctx| ctx.get_text()).ok();
+    let rendered = snippet
+        .render(&RenderContext {
+            clipboard: clipboard.as_deref(),
+            inputs: &inputs,
+        })
+        .map_err(|e| e.to_string())?;
+
+    if copy {
+        copy_to_clipboard(rendered.text.clone(), writes)?;
+    }
+    Ok(rendered)
+}
+
+#[tauri::command]
+fn import_snippets(path: String, state: tauri::State<SharedSnippets>) -> Result<usize, String> {
+    state.lock().unwrap().import_file(Path::new(&path)).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn export_snippets(path: String, state: tauri::State<SharedSnippets>) -> Result<(), String> {
+    state.lock().unwrap().export_file(Path::new(&path)).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn sync_snippets(
+    state: tauri::State<'_, ClipboardSyncContainer>,
+    snippets: tauri::State<'_, SharedSnippets>,
+) -> Result<(), String> {
+    let sync_engine = state.lock().ok()
+        .and_then(|container| container.clone())
+        .ok_or_else(|| "同步引擎未初始化".to_string())?;
+    sync_snippet_library(&sync_engine, &snippets).await.map_err(|e| e.to_string())
+}
+
+// 下载远端片段库并与本地合并，有本地修改时再上传合并结果
+async fn sync_snippet_library(sync_engine: &SyncEngine, snippets: &SharedSnippets) -> anyhow::Result<()> {
+    let remote = sync_engine.download_snippets().await?;
+    let remote_versions: HashMap<String, u64> = remote.iter()
+        .map(|snippet| (snippet.id.clone(), snippet.updated_at))
+        .collect();
+
+    let merged = {
+        let mut store = snippets.lock().unwrap();
+        store.merge(remote)?;
+        store.all().to_vec()
+    };
+
+    // 本地有远端没有的片段或更新的版本时才上传
+    let local_newer = merged.iter().any(|snippet| {
+        remote_versions.get(&snippet.id).is_none_or(|updated_at| snippet.updated_at > *updated_at)
+    });
+    if local_newer {
+        sync_engine.upload_snippets(&merged).await?;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+fn copy_to_clipboard(content: String, writes: tauri::State<ClipboardWrites>) -> Result<(), String> {
     let ctx = ClipboardContext::new().map_err(|e| e.to_string())?;
+    writes.register(loopback::text_fingerprint(&content), None);
     ctx.set_text(content).map_err(|e| e.to_string())?;
     Ok(())
 }
 
 #[tauri::command]
-fn copy_image_to_clipboard(base64_data: String) -> Result<(), String> {
-    let ctx = ClipboardContext::new().map_err(|e| e.to_string())?;
-    
-    // 解码 base64 数据
+fn copy_image_to_clipboard(base64_data: String, writes: tauri::State<ClipboardWrites>) -> Result<(), String> {
+    // 解码 base64 数据，兼容 data URL 形式
+    let base64_data = match base64_data.split_once(";base64,") {
+        Some((_, data)) => data.to_string(),
+        None => base64_data,
+    };
     let image_bytes = general_purpose::STANDARD
-        .decode(base64_data)
+        .decode(base64_data.trim())
         .map_err(|e| format!("Failed to decode base64: {}", e))?;
     
-    // 创建 RustImageData (简化处理)
-    ctx.set_text(format!("图片数据 ({} 字节)", image_bytes.len())).map_err(|e| e.to_string())?;
-    Ok(())
+    set_image_on_clipboard(&image_bytes, &writes, None)
+}
+
+#[tauri::command]
+fn copy_clipboard_item(
+    item_id: String,
+    state: tauri::State<ClipboardStorage>,
+    writes: tauri::State<ClipboardWrites>,
+) -> Result<(), String> {
+    write_item_to_clipboard(&item_id, &state, &writes, false)
+}
+
+#[tauri::command]
+fn copy_item_as_plain_text(
+    item_id: String,
+    state: tauri::State<ClipboardStorage>,
+    writes: tauri::State<ClipboardWrites>,
+) -> Result<(), String> {
+    write_item_to_clipboard(&item_id, &state, &writes, true)
+}
+
+// 把历史项目写回剪贴板，所有格式一起写入以保留格式；plain_text 为 true 时只写入纯文本
+fn write_item_to_clipboard(
+    item_id: &str,
+    storage: &ClipboardStorage,
+    writes: &WriteRegistry,
+    plain_text: bool,
+) -> Result<(), String> {
+    let item = storage.lock().unwrap()
+        .get(item_id)
+        .ok_or_else(|| format!("项目不存在: {}", item_id))?;
+    
+    match item.item_type.as_str() {
+        "files" => {
+            let file_paths = restore_missing_files(
+                &item.file_paths.unwrap_or_default(),
+                &item.file_types.unwrap_or_default(),
+                storage,
+            )?;
+            set_files_on_clipboard(file_paths, writes, Some(item.id))
+        }
+        "image" => {
+            let image = item.image.ok_or_else(|| "图片信息缺失".to_string())?;
+            let image_bytes = storage.lock().unwrap()
+                .blobs()
+                .get(&image.hash)
+                .map_err(|e| format!("读取图片数据失败: {}", e))?;
+            set_image_on_clipboard(&image_bytes, writes, Some(item.id))
+        }
+        _ => {
+            let ctx = ClipboardContext::new().map_err(|e| e.to_string())?;
+            writes.register(loopback::text_fingerprint(&item.content), Some(item.id));
+            let mut contents = vec![ClipboardContent::Text(item.content)];
+            if !plain_text {
+                if let Some(html) = item.html {
+                    contents.push(ClipboardContent::Html(html));
+                }
+                if let Some(rtf) = item.rtf {
+                    contents.push(ClipboardContent::Rtf(rtf));
+                }
+            }
+            ctx.set(contents).map_err(|e| e.to_string())
+        }
+    }
+}
+
+// 识别格式、解码图片并写入剪贴板
+fn set_image_on_clipboard(image_bytes: &[u8], writes: &WriteRegistry, item_id: Option<String>) -> Result<(), String> {
+    let image = image_codec::decode_image(image_bytes)?;
+    let ctx = ClipboardContext::new().map_err(|e| e.to_string())?;
+    writes.register(loopback::image_fingerprint(image.as_bytes(), image.width(), image.height()), item_id);
+    ctx.set_image(RustImageData::from_dynamic_image(image)).map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-fn copy_files_to_clipboard(file_paths: Vec<String>) -> Result<(), String> {
+fn copy_files_to_clipboard(
+    file_paths: Vec<String>,
+    state: tauri::State<ClipboardStorage>,
+    writes: tauri::State<ClipboardWrites>,
+) -> Result<(), String> {
+    // 原文件不存在时，使用历史中同一组文件保留的副本
+    let file_types = state.lock().unwrap()
+        .find(|item| item.file_paths.as_ref() == Some(&file_paths))
+        .and_then(|item| item.file_types)
+        .unwrap_or_default();
+    let file_paths = restore_missing_files(&file_paths, &file_types, &state)?;
+    set_files_on_clipboard(file_paths, &writes, None)
+}
+
+// 把已不存在的文件从副本恢复到应用数据目录下，返回可以写入剪贴板的路径
+fn restore_missing_files(
+    file_paths: &[String],
+    file_types: &[FileTypeInfo],
+    storage: &ClipboardStorage,
+) -> Result<Vec<String>, String> {
+    let blobs = storage.lock().unwrap().blobs().clone();
+    file_snapshot::resolve_file_paths(file_paths, file_types, &blobs, &get_app_data_dir().join("restored"))
+}
+
+fn set_files_on_clipboard(file_paths: Vec<String>, writes: &WriteRegistry, item_id: Option<String>) -> Result<(), String> {
     let ctx = ClipboardContext::new().map_err(|e| e.to_string())?;
     
     // 验证所有文件路径存在
@@ -83,32 +533,255 @@ fn copy_files_to_clipboard(file_paths: Vec<String>) -> Result<(), String> {
         }
     }
     
+    writes.register(loopback::files_fingerprint(&file_paths), item_id);
     ctx.set_files(file_paths).map_err(|e| e.to_string())?;
     Ok(())
 }
 
+// 记录状态相关命令
+#[tauri::command]
+fn get_capture_state(app_handle: AppHandle) -> CaptureStatus {
+    current_capture_status(&app_handle)
+}
+
+#[tauri::command]
+fn pause_capture(app_handle: AppHandle) -> Result<CaptureStatus, String> {
+    update_capture_state(&app_handle, CaptureState::paused())
+}
+
+#[tauri::command]
+fn resume_capture(app_handle: AppHandle) -> Result<CaptureStatus, String> {
+    update_capture_state(&app_handle, CaptureState::default())
+}
+
+#[tauri::command]
+fn start_incognito(minutes: u64, app_handle: AppHandle) -> Result<CaptureStatus, String> {
+    if minutes == 0 {
+        return Err("无痕模式时长必须大于 0".to_string());
+    }
+    update_capture_state(&app_handle, CaptureState::incognito(unix_now(), minutes))
+}
+
+#[tauri::command]
+fn restart_clipboard_monitor(app_handle: AppHandle) -> Result<CaptureStatus, String> {
+    let monitor = app_handle.state::<SharedMonitor>().inner().clone();
+    monitor.lock().unwrap().stop();
+    apply_capture_state(&app_handle)
+}
+
+// 更新记录状态：保存到文件后按新状态启动或停止监听线程
+fn update_capture_state(app_handle: &AppHandle, new_state: CaptureState) -> Result<CaptureStatus, String> {
+    new_state
+        .save_to_file(&get_app_data_dir().join("capture_state.json"))
+        .map_err(|e| e.to_string())?;
+    
+    let capture_state = app_handle.state::<SharedCaptureState>().inner().clone();
+    *capture_state.lock().unwrap() = new_state;
+    
+    apply_capture_state(app_handle)
+}
+
+// 根据当前记录状态启动或停止监听线程，并通知前端和托盘
+fn apply_capture_state(app_handle: &AppHandle) -> Result<CaptureStatus, String> {
+    let capture_state = app_handle.state::<SharedCaptureState>().inner().clone();
+    let state = capture_state.lock().unwrap().clone();
+    
+    // 无痕模式已到期，恢复为正常记录
+    if state.paused && state.is_capturing(unix_now()) {
+        return update_capture_state(app_handle, CaptureState::default());
+    }
+    
+    {
+        let monitor = app_handle.state::<SharedMonitor>().inner().clone();
+        let mut monitor = monitor.lock().unwrap();
+        if state.paused {
+            monitor.stop();
+        } else {
+            monitor.start()?;
+        }
+    }
+    
+    if let Some(until) = state.incognito_until {
+        schedule_auto_resume(app_handle.clone(), until);
+    }
+    
+    let status = current_capture_status(app_handle);
+    if let Some(toggle) = app_handle.try_state::<CaptureToggleMenuItem>() {
+        let _ = toggle.0.set_text(if status.paused { "恢复记录" } else { "暂停记录" });
+    }
+    let _ = app_handle.emit("capture-state-changed", &status);
+    
+    Ok(status)
+}
+
+fn current_capture_status(app_handle: &AppHandle) -> CaptureStatus {
+    let state = app_handle.state::<SharedCaptureState>().inner().lock().unwrap().clone();
+    let watcher_running = app_handle.state::<SharedMonitor>().inner().lock().unwrap().is_running();
+    
+    CaptureStatus {
+        paused: state.paused,
+        incognito_until: state.incognito_until,
+        watcher_running,
+    }
+}
+
+// 无痕模式到期后自动恢复记录
+fn schedule_auto_resume(app_handle: AppHandle, until: u64) {
+    tauri::async_runtime::spawn(async move {
+        let wait = until.saturating_sub(unix_now());
+        tokio::time::sleep(std::time::Duration::from_secs(wait)).await;
+        
+        // 期间状态可能被手动修改过，只有仍处于同一次无痕模式时才恢复
+        let capture_state = app_handle.state::<SharedCaptureState>().inner().clone();
+        let still_incognito = capture_state.lock().unwrap().incognito_until == Some(until);
+        if still_incognito {
+            if let Err(e) = update_capture_state(&app_handle, CaptureState::default()) {
+                tracing::error!("自动恢复记录失败: {}", e);
+            }
+        }
+    });
+}
+
+// 粘贴队列相关命令
+#[tauri::command]
+fn get_paste_queue(state: tauri::State<SharedPasteQueue>) -> PasteQueueStatus {
+    state.lock().unwrap().status()
+}
+
+#[tauri::command]
+fn start_paste_queue(order: QueueOrder, app_handle: AppHandle) -> Result<PasteQueueStatus, String> {
+    update_paste_queue(&app_handle, |queue| *queue = PasteQueue::start(order))
+}
+
+#[tauri::command]
+fn stop_paste_queue(app_handle: AppHandle) -> Result<PasteQueueStatus, String> {
+    update_paste_queue(&app_handle, |queue| *queue = PasteQueue::default())
+}
+
+// 把队列中的下一个项目写入剪贴板；已被删除的项目直接跳过，写入失败时项目留在队列中
+#[tauri::command]
+fn paste_queue_next(
+    app_handle: AppHandle,
+    state: tauri::State<ClipboardStorage>,
+    writes: tauri::State<ClipboardWrites>,
+) -> Result<PasteQueueStatus, String> {
+    let mut result = Err("粘贴队列为空".to_string());
+    let status = update_paste_queue(&app_handle, |queue| {
+        while let Some(item_id) = queue.peek().cloned() {
+            if state.lock().unwrap().get(&item_id).is_none() {
+                queue.next();
+                continue;
+            }
+            result = write_item_to_clipboard(&item_id, &state, &writes, false);
+            if result.is_ok() {
+                queue.next();
+            }
+            break;
+        }
+    })?;
+
+    result.map(|_| status)
+}
+
+// 修改粘贴队列，保存到文件并通知前端
+fn update_paste_queue<F: FnOnce(&mut PasteQueue)>(app_handle: &AppHandle, update: F) -> Result<PasteQueueStatus, String> {
+    let queue = app_handle.state::<SharedPasteQueue>().inner().clone();
+    let status = {
+        let mut queue = queue.lock().unwrap();
+        update(&mut queue);
+        queue
+            .save_to_file(&get_app_data_dir().join("paste_queue.json"))
+            .map_err(|e| e.to_string())?;
+        queue.status()
+    };
+    let _ = app_handle.emit("paste-queue-changed", &status);
+    Ok(status)
+}
+
+// 检查到期项目的间隔
+const EXPIRY_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+// 每分钟删除一次到期的项目，并通知前端移除
+fn start_expiry_sweeper(app_handle: AppHandle) {
+    thread::spawn(move || loop {
+        let expired = app_handle.state::<ClipboardStorage>().lock().unwrap().remove_expired(unix_now());
+        match expired {
+            Ok(expired) if !expired.is_empty() => {
+                let item_ids: Vec<&String> = expired.iter().map(|item| &item.id).collect();
+                let _ = app_handle.emit("clipboard-items-removed", &item_ids);
+                sync_local_deletes(&app_handle, expired);
+            }
+            Ok(_) => {}
+            Err(e) => eprintln!("删除到期项目失败: {}", e),
+        }
+        thread::sleep(EXPIRY_CHECK_INTERVAL);
+    });
+}
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
 // 同步相关命令
 #[tauri::command]
 async fn setup_sync(
     _user_id: String,
     _storage_config: serde_json::Value,
-    state: tauri::State<'_, ClipboardSyncContainer>
+    state: tauri::State<'_, ClipboardSyncContainer>,
+    app_handle: AppHandle,
 ) -> Result<(), String> {
     // 重新初始化同步引擎（配置已经通过configure_storage保存了）
-    let sync_engine = create_sync_engine_if_configured().await;
-    
-    if let Ok(mut container) = state.lock() {
-        *container = sync_engine;
-        Ok(())
-    } else {
-        Err("无法更新同步引擎".to_string())
-    }
+    replace_sync_engine(&state, &app_handle).await
 }
 
 #[tauri::command]
-async fn sync_now(state: tauri::State<'_, ClipboardSyncContainer>) -> Result<(), String> {
+async fn sync_now(
+    state: tauri::State<'_, ClipboardSyncContainer>,
+    snippets: tauri::State<'_, SharedSnippets>,
+) -> Result<(), String> {
+    run_sync_now(&state, &snippets).await
+}
+
+// 在后台把本地删除的项目同步到其他设备
+fn sync_local_deletes(app_handle: &AppHandle, items: Vec<ClipboardItem>) {
+    let sync_engine = app_handle.state::<ClipboardSyncContainer>().lock().ok()
+        .and_then(|container| container.clone());
+    let Some(sync_engine) = sync_engine else {
+        return;
+    };
+    let app_handle = app_handle.clone();
+    tauri::async_runtime::spawn(async move {
+        // 上传失败时操作保留在发件箱中，后台同步时重试
+        if let Err(e) = push_local_deletes(&sync_engine, &items).await {
+            eprintln!("同步删除项目失败: {}", e);
+            let _ = app_handle.emit("sync-error", format!("同步失败: {}", e));
+        }
+    });
+}
+
+// 生成删除操作；规则排除同步的项目只保存在本机，不需要同步。
+// 一次删除多个项目（清空历史、多个项目同时到期）时只生成一个批量操作
+async fn push_local_deletes(sync_engine: &SyncEngine, items: &[ClipboardItem]) -> Result<(), String> {
+    let item_ids: Vec<String> = items
+        .iter()
+        .filter(|item| !item.exclude_from_sync)
+        .map(|item| item.id.clone())
+        .collect();
+    let result = match item_ids.as_slice() {
+        [] => return Ok(()),
+        [item_id] => sync_engine.local_delete(item_id.clone()).await,
+        _ => sync_engine.local_delete_batch(item_ids).await,
+    };
+    result.map_err(|e| e.to_string())
+}
+
+// 立即同步历史和片段库，命令和自动化接口共用
+async fn run_sync_now(container: &ClipboardSyncContainer, snippets: &SharedSnippets) -> Result<(), String> {
     let sync_engine_clone = {
-        if let Ok(sync_engine_opt) = state.lock() {
+        if let Ok(sync_engine_opt) = container.lock() {
             sync_engine_opt.clone()
         } else {
             return Err("无法获取同步引擎".to_string());
@@ -116,7 +789,8 @@ async fn sync_now(state: tauri::State<'_, ClipboardSyncContainer>) -> Result<(),
     };
     
     if let Some(sync_engine) = sync_engine_clone {
-        sync_engine.sync_now().await.map_err(|e| e.to_string())
+        sync_engine.sync_now().await.map_err(|e| e.to_string())?;
+        sync_snippet_library(&sync_engine, snippets).await.map_err(|e| e.to_string())
     } else {
         Err("同步引擎未初始化".to_string())
     }
@@ -170,17 +844,40 @@ async fn configure_storage(
 /// 重新加载同步引擎
 async fn reload_sync_engine(app_handle: &AppHandle) -> Result<(), String> {
     let sync_engine_container: tauri::State<ClipboardSyncContainer> = app_handle.state();
-    let sync_engine = create_sync_engine_if_configured().await;
-    
-    {
-        if let Ok(mut container) = sync_engine_container.lock() {
-            *container = sync_engine;
-            tracing::info!("同步引擎已重新加载");
-        } else {
-            return Err("无法更新同步引擎".to_string());
+    replace_sync_engine(&sync_engine_container, app_handle).await?;
+    tracing::info!("同步引擎已重新加载");
+    Ok(())
+}
+
+// 启动时的初始化、setup_sync 和 configure_storage 可能同时创建同步引擎，依次执行
+static SYNC_ENGINE_INSTALL: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+// 先停止旧引擎并上传剩余的操作，再按当前配置创建新引擎；
+// 两个引擎同时运行会互相覆盖发件箱、时钟和同步状态文件
+async fn replace_sync_engine(container: &ClipboardSyncContainer, app_handle: &AppHandle) -> Result<(), String> {
+    install_sync_engine(container, true, || create_sync_engine_if_configured(app_handle)).await
+}
+
+// 在容器中安装 create 创建的引擎；replace 为 false 时容器中已有引擎则不再创建
+async fn install_sync_engine<F, Fut>(container: &ClipboardSyncContainer, replace: bool, create: F) -> Result<(), String>
+where
+    F: FnOnce() -> Fut,
+    Fut: std::future::Future<Output = Option<ClipboardSync>>,
+{
+    let _installing = SYNC_ENGINE_INSTALL.lock().await;
+    let old_engine = {
+        let mut container = container.lock().map_err(|_| "无法更新同步引擎".to_string())?;
+        if container.is_some() && !replace {
+            return Ok(());
         }
+        container.take()
+    };
+    if let Some(old_engine) = old_engine {
+        old_engine.shutdown().await;
     }
-    
+
+    let sync_engine = create().await;
+    *container.lock().map_err(|_| "无法更新同步引擎".to_string())? = sync_engine;
     Ok(())
 }
 
@@ -255,47 +952,80 @@ fn hide_sensitive_fields(backend: &mut serde_json::Value) {
     }
 }
 
-// 计算文件大小
-fn calculate_files_size(file_paths: &[String]) -> u64 {
-    file_paths.iter()
-        .filter_map(|path| std::fs::metadata(path).ok())
-        .map(|metadata| metadata.len())
-        .sum()
+/// 远端同步写入本地历史的变化，随 sync-items-changed 事件发送给前端
+#[derive(Debug, Default, serde::Serialize)]
+struct SyncItemsChanged {
+    added: Vec<ClipboardItem>,
+    removed: Vec<String>,
 }
 
-// 剪切板管理器
-struct ClipboardManager {
-    ctx: ClipboardContext,
+impl SyncItemsChanged {
+    fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+// 把其他设备的变化写入本地历史：保留原来的项目 id，直接写入存储而不经过同步引擎，
+// 因此不会再次上传；删除写入删除记录。返回实际发生的变化
+fn apply_remote_changes(storage: &mut StorageEngine, delta: &SyncDelta) -> SyncItemsChanged {
+    let mut changes = SyncItemsChanged::default();
+
+    for remote in &delta.added {
+        let mut item = ClipboardItem::from(remote);
+        if let Some(existing) = storage.get(&item.id) {
+            // 本地已有相同或更新的版本（例如之后又在本机使用过）
+            if existing.timestamp >= item.timestamp {
+                continue;
+            }
+            // 标签、置顶等只在本机记录的字段保持不变
+            item.use_count = existing.use_count;
+            item.tags = existing.tags;
+            item.pinned = existing.pinned;
+            item.expires_at = existing.expires_at;
+            item.exclude_from_sync = existing.exclude_from_sync;
+        }
+        match storage.insert(&item) {
+            Ok(()) => changes.added.push(item),
+            Err(e) => eprintln!("写入同步项目失败: {}", e),
+        }
+    }
+
+    for item_id in &delta.removed {
+        if storage.get(item_id).is_none() {
+            continue;
+        }
+        match storage.delete(item_id) {
+            Ok(()) => changes.removed.push(item_id.clone()),
+            Err(e) => eprintln!("删除同步项目失败: {}", e),
+        }
+    }
+
+    changes
+}
+
+// 将新项目写入历史记录并同步，可以在监听线程之外使用
+#[derive(Clone)]
+struct HistoryRecorder {
     app_handle: AppHandle,
     storage: ClipboardStorage,
-    sync_engine: Option<ClipboardSync>,
-    runtime_handle: tokio::runtime::Handle,
-    last_text: String,
-    last_files: Vec<String>,
+    sync_engine: ClipboardSyncContainer,
 }
 
-impl ClipboardManager {
-    pub fn new(
-        app_handle: AppHandle, 
-        storage: ClipboardStorage, 
-        sync_engine: Option<ClipboardSync>,
-        runtime_handle: tokio::runtime::Handle
-    ) -> Result<Self, String> {
-        let ctx = ClipboardContext::new()
-            .map_err(|e| format!("Failed to create clipboard context: {}", e))?;
-
-        Ok(Self {
-            ctx,
-            app_handle,
-            storage,
-            sync_engine,
-            runtime_handle,
-            last_text: String::new(),
-            last_files: Vec::new(),
-        })
+impl HistoryRecorder {
+    fn from_app(app_handle: &AppHandle) -> Self {
+        Self {
+            app_handle: app_handle.clone(),
+            storage: app_handle.state::<ClipboardStorage>().inner().clone(),
+            sync_engine: app_handle.state::<ClipboardSyncContainer>().inner().clone(),
+        }
     }
 
-    fn add_item_to_history(&self, item: ClipboardItem) {
+    fn record(&self, item: ClipboardItem) {
+        // 同步引擎可能在运行中被重新加载，每次使用时从容器中获取；规则排除同步的项目只保存在本机
+        let sync_engine = self.sync_engine.lock().ok()
+            .and_then(|container| container.clone())
+            .filter(|_| !item.exclude_from_sync);
+        
         // 将项目存储到持久化存储中
         if let Ok(mut storage_lock) = self.storage.lock() {
             if let Err(e) = storage_lock.insert(&item) {
@@ -303,15 +1033,17 @@ impl ClipboardManager {
             }
         }
 
-        // 如果启用了同步，也添加到同步引擎
-        if let Some(sync_engine) = &self.sync_engine {
+        // 如果启用了同步，也添加到同步引擎；图片数据和文件副本随操作一起上传
+        if let Some(sync_engine) = sync_engine {
             let sync_item = SyncClipboardItem::from(&item);
+            let blobs = item.blob_hashes();
             let app_handle_clone = self.app_handle.clone();
             let sync_engine_clone = sync_engine.clone();
             
-            // 使用运行时句柄来执行异步操作
-            self.runtime_handle.spawn(async move {
-                if let Err(e) = sync_engine_clone.local_add(sync_item).await {
+            // 在 Tauri 的异步运行时中执行异步操作
+            tauri::async_runtime::spawn(async move {
+                // 上传失败时操作保留在发件箱中，后台同步时重试
+                if let Err(e) = sync_engine_clone.local_add(sync_item, blobs).await {
                     eprintln!("同步添加项目失败: {}", e);
                     // 可以发送错误事件到前端
                     let _ = app_handle_clone.emit("sync-error", format!("同步失败: {}", e));
@@ -321,11 +1053,284 @@ impl ClipboardManager {
         
         // 发送事件到前端
         let _ = self.app_handle.emit("clipboard-update", &item);
+
+        // 粘贴队列开启时加入队列
+        let queue_active = self.app_handle
+            .try_state::<SharedPasteQueue>()
+            .is_some_and(|queue| queue.lock().unwrap().active);
+        if queue_active {
+            if let Err(e) = update_paste_queue(&self.app_handle, |queue| {
+                queue.push(item.id.clone());
+            }) {
+                eprintln!("加入粘贴队列失败: {}", e);
+            }
+        }
+    }
+}
+
+impl HistoryRecorder {
+    // 先按用户规则处理剪贴板中新复制的项目，再写入历史；被规则跳过时不保存
+    fn record_captured(&self, rules: &Mutex<RuleEngine>, mut item: ClipboardItem) {
+        let outcome = rules.lock().unwrap().evaluate(&mut item);
+        for command in outcome.commands {
+            rules::spawn_command(command, &item);
+        }
+        if outcome.skip {
+            tracing::debug!("项目被规则跳过: {}", outcome.matched.join(", "));
+            return;
+        }
+        self.record(item);
+    }
+}
+
+// 自动化接口的方法，与对应的 Tauri 命令行为一致
+#[cfg(unix)]
+struct AppIpcHandler {
+    app_handle: AppHandle,
+}
+
+#[cfg(unix)]
+#[derive(serde::Deserialize)]
+struct IpcItemParams {
+    id: String,
+    #[serde(default)]
+    plain_text: bool,
+}
+
+#[cfg(unix)]
+#[derive(serde::Deserialize)]
+struct IpcPushParams {
+    content: String,
+    #[serde(default)]
+    source_app: Option<String>,
+}
+
+#[cfg(unix)]
+impl ipc::IpcHandler for AppIpcHandler {
+    async fn call(&self, method: &str, params: serde_json::Value) -> Result<serde_json::Value, ipc::RpcError> {
+        let storage = self.app_handle.state::<ClipboardStorage>().inner().clone();
+        match method {
+            "query" => {
+                let query: Option<HistoryQuery> = ipc::parse_params(params)?;
+                let items = query.unwrap_or_default().run(&storage.lock().unwrap());
+                ipc_result(&items)
+            }
+            "get" => {
+                let params: IpcItemParams = ipc::parse_params(params)?;
+                let item = storage.lock().unwrap()
+                    .get(&params.id)
+                    .ok_or_else(|| format!("项目不存在: {}", params.id))?;
+                ipc_result(&item)
+            }
+            // 直接加入历史，不修改剪贴板
+            "push" => {
+                let params: IpcPushParams = ipc::parse_params(params)?;
+                if params.content.trim().is_empty() {
+                    return Err(ipc::RpcError::invalid_params("内容为空"));
+                }
+                let item = ClipboardItem {
+                    id: uuid::Uuid::new_v4().to_string(),
+                    timestamp: unix_now(),
+                    item_type: "text".to_string(),
+                    size: Some(params.content.len() as u64),
+                    source_app: params.source_app,
+                    detected_kind: text_classify::classify(&params.content),
+                    content: params.content,
+                    ..Default::default()
+                };
+                HistoryRecorder::from_app(&self.app_handle).record(item.clone());
+                ipc_result(&item)
+            }
+            "copy" => {
+                let params: IpcItemParams = ipc::parse_params(params)?;
+                let writes = self.app_handle.state::<ClipboardWrites>().inner().clone();
+                write_item_to_clipboard(&params.id, &storage, &writes, params.plain_text)?;
+                Ok(serde_json::Value::Bool(true))
+            }
+            "sync_now" => {
+                let container = self.app_handle.state::<ClipboardSyncContainer>().inner().clone();
+                let snippets = self.app_handle.state::<SharedSnippets>().inner().clone();
+                run_sync_now(&container, &snippets).await?;
+                Ok(serde_json::Value::Bool(true))
+            }
+            // 再次启动应用时由新进程调用，显示已运行实例的主窗口
+            "show_window" => {
+                let window = self.app_handle.get_webview_window("main").ok_or_else(|| "主窗口不存在".to_string())?;
+                window.show().map_err(|e| e.to_string())?;
+                window.set_focus().map_err(|e| e.to_string())?;
+                Ok(serde_json::Value::Bool(true))
+            }
+            other => Err(ipc::RpcError::method_not_found(other)),
+        }
     }
+}
+
+#[cfg(unix)]
+fn ipc_result<T: serde::Serialize>(value: &T) -> Result<serde_json::Value, ipc::RpcError> {
+    serde_json::to_value(value).map_err(|e| ipc::RpcError::from(e.to_string()))
+}
+
+// 转发给自动化接口订阅者的事件
+#[cfg(unix)]
+const IPC_EVENTS: &[&str] = &["clipboard-update", "clipboard-items-removed", "sync-items-changed", "capture-state-changed", "paste-queue-changed", "sync-error"];
+
+// 在应用数据目录下启动自动化接口；失败时只记录日志，不影响应用运行
+#[cfg(unix)]
+fn start_ipc_server(app_handle: &AppHandle) {
+    use tauri::Listener;
+
+    let app_handle = app_handle.clone();
+    tauri::async_runtime::spawn(async move {
+        let handler = AppIpcHandler { app_handle: app_handle.clone() };
+        let server = match ipc::IpcServer::bind(&get_app_data_dir(), handler) {
+            Ok(server) => server,
+            Err(e) => {
+                eprintln!("启动自动化接口失败: {:#}", e);
+                return;
+            }
+        };
 
-    fn check_text_change(&mut self) {
+        // 前端事件的内容已经序列化为 JSON，解析后原样转发
+        let events = server.events();
+        for event in IPC_EVENTS {
+            let events = events.clone();
+            app_handle.listen_any(*event, move |emitted| {
+                let payload = serde_json::from_str(emitted.payload()).unwrap_or(serde_json::Value::Null);
+                let _ = events.send(ipc::IpcEvent { event: event.to_string(), payload });
+            });
+        }
+
+        tracing::info!("自动化接口已启动: {}", server.socket_path().display());
+        if let Err(e) = server.serve().await {
+            eprintln!("自动化接口停止: {}", e);
+        }
+    });
+}
+
+// 剪切板管理器
+struct ClipboardManager {
+    ctx: ClipboardContext,
+    app_handle: AppHandle,
+    storage: ClipboardStorage,
+    sync_engine: ClipboardSyncContainer,
+    settings: SharedSettings,
+    window_provider: Box<dyn WindowInfoProvider>,
+    writes: ClipboardWrites,
+    file_types: Arc<FileTypeRegistry>,
+    rules: SharedRules,
+    last_text: String,
+    last_files: Vec<String>,
+    last_image_hash: Option<String>,
+}
+
+impl ClipboardManager {
+    pub fn new(
+        recorder: HistoryRecorder,
+        settings: SharedSettings,
+        window_provider: Box<dyn WindowInfoProvider>,
+        writes: ClipboardWrites,
+        file_types: Arc<FileTypeRegistry>,
+        rules: SharedRules,
+    ) -> Result<Self, String> {
+        let ctx = ClipboardContext::new()
+            .map_err(|e| format!("Failed to create clipboard context: {}", e))?;
+
+        Ok(Self {
+            ctx,
+            app_handle: recorder.app_handle,
+            storage: recorder.storage,
+            sync_engine: recorder.sync_engine,
+            settings,
+            window_provider,
+            writes,
+            file_types,
+            rules,
+            last_text: String::new(),
+            last_files: Vec::new(),
+            last_image_hash: None,
+        })
+    }
+
+    fn recorder(&self) -> HistoryRecorder {
+        HistoryRecorder {
+            app_handle: self.app_handle.clone(),
+            storage: self.storage.clone(),
+            sync_engine: self.sync_engine.clone(),
+        }
+    }
+
+    fn add_item_to_history(&self, item: ClipboardItem) {
+        self.recorder().record_captured(&self.rules, item);
+    }
+
+    // 检查变化是否由 Clippy 自己写入剪贴板引起；是则只更新对应项目的使用记录，不再新建项目
+    fn handle_loopback<F: Fn(&ClipboardItem) -> bool>(&self, fingerprint: &str, matches: F) -> bool {
+        let item_id = match self.writes.take(fingerprint) {
+            Some(item_id) => item_id,
+            None => return false,
+        };
+        
+        let touched = match self.storage.lock() {
+            Ok(mut storage_lock) => {
+                // 未登记项目时按内容查找对应的历史项目
+                match item_id.or_else(|| storage_lock.find(&matches).map(|item| item.id)) {
+                    Some(item_id) => storage_lock.touch(&item_id).unwrap_or_else(|e| {
+                        eprintln!("更新项目使用记录失败: {}", e);
+                        None
+                    }),
+                    None => None,
+                }
+            }
+            Err(_) => None,
+        };
+        
+        if let Some(item) = touched {
+            let _ = self.app_handle.emit("clipboard-update", &item);
+        }
+        true
+    }
+
+    // 来源应用被过滤时，把当前内容记为已处理，避免之后的变化通知把它记录下来
+    fn mark_current_as_seen(&mut self) {
+        if self.ctx.has(ContentFormat::Files) {
+            if let Ok(files) = self.ctx.get_files() {
+                self.last_files = files;
+            }
+        } else if self.ctx.has(ContentFormat::Text) {
+            if let Ok(text) = self.ctx.get_text() {
+                self.last_text = text;
+            }
+        } else if let Ok(image) = self.ctx.get_image() {
+            if let Ok(captured) = image_codec::encode_captured_image(&image) {
+                self.last_image_hash = Some(blob_store::content_hash(&captured.png));
+            }
+        }
+    }
+
+    // 读取与文本一起复制的其他格式，空内容视为不存在
+    fn read_rich_format(&self, format: ContentFormat) -> Option<String> {
+        if !self.ctx.has(format.clone()) {
+            return None;
+        }
+        let content = match format {
+            ContentFormat::Html => self.ctx.get_html(),
+            ContentFormat::Rtf => self.ctx.get_rich_text(),
+            _ => return None,
+        };
+        content.ok().filter(|content| !content.trim().is_empty())
+    }
+
+    fn check_text_change(&mut self, source_app: Option<String>) {
         if let Ok(text) = self.ctx.get_text() {
             if text != self.last_text && !text.trim().is_empty() {
+                if self.handle_loopback(&loopback::text_fingerprint(&text), |item| item.item_type == "text" && item.content == text) {
+                    self.last_text = text;
+                    return;
+                }
+                
+                let html = self.read_rich_format(ContentFormat::Html);
+                let rtf = self.read_rich_format(ContentFormat::Rtf);
+                
                 let item = ClipboardItem {
                     id: uuid::Uuid::new_v4().to_string(),
                     content: text.clone(),
@@ -335,8 +1340,11 @@ impl ClipboardManager {
                         .as_secs(),
                     item_type: "text".to_string(),
                     size: Some(text.len() as u64),
-                    file_paths: None,
-                    file_types: None,
+                    source_app,
+                    html,
+                    rtf,
+                    detected_kind: text_classify::classify(&text),
+                    ..Default::default()
                 };
                 
                 self.add_item_to_history(item);
@@ -345,172 +1353,272 @@ impl ClipboardManager {
         }
     }
 
-    fn check_files_change(&mut self) {
+    fn check_files_change(&mut self, source_app: Option<String>) {
         if let Ok(files) = self.ctx.get_files() {
             if files != self.last_files && !files.is_empty() {
-                let total_size = calculate_files_size(&files);
-                
-                // 检测文件类型
-                let file_types: Vec<FileTypeInfo> = files.iter()
-                    .map(|file_path| detect_file_type(file_path))
-                    .collect();
-                
-                let item = ClipboardItem {
-                    id: uuid::Uuid::new_v4().to_string(),
-                    content: format!("{} 个文件", files.len()),
-                    timestamp: std::time::SystemTime::now()
-                        .duration_since(std::time::UNIX_EPOCH)
-                        .unwrap()
-                        .as_secs(),
-                    item_type: "files".to_string(),
-                    size: Some(total_size),
-                    file_paths: Some(files.clone()),
-                    file_types: Some(file_types),
-                };
+                if self.handle_loopback(&loopback::files_fingerprint(&files), |item| item.file_paths.as_ref() == Some(&files)) {
+                    self.last_files = files;
+                    return;
+                }
                 
-                self.add_item_to_history(item);
+                // 记录复制发生的时间，检测完成后才写入历史
+                let timestamp = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs();
+                let recorder = self.recorder();
+                let file_types = self.file_types.clone();
+                let rules = self.rules.clone();
+                let snapshots = self.settings.lock().unwrap().file_snapshots.clone();
+                let paths = files.clone();
+
+                // 目录统计和读取网络盘可能很慢，放到阻塞线程池中检测，不阻塞剪贴板监听
+                tauri::async_runtime::spawn_blocking(move || {
+                    let mut file_types = file_detect::detect_file_types(&paths, &file_types);
+                    let total_size = file_types.iter().map(|info| info.size).sum();
+
+                    if snapshots.enabled {
+                        let blobs = recorder.storage.lock().unwrap().blobs().clone();
+                        file_snapshot::snapshot_files(&mut file_types, &blobs, &snapshots);
+                    }
+
+                    let item = ClipboardItem {
+                        id: uuid::Uuid::new_v4().to_string(),
+                        content: format!("{} 个文件", paths.len()),
+                        timestamp,
+                        item_type: "files".to_string(),
+                        size: Some(total_size),
+                        file_paths: Some(paths),
+                        file_types: Some(file_types),
+                        source_app,
+                        ..Default::default()
+                    };
+
+                    recorder.record_captured(&rules, item);
+                });
                 self.last_files = files;
             }
         }
     }
+
+    fn check_image_change(&mut self, source_app: Option<String>) {
+        let image = match self.ctx.get_image() {
+            Ok(image) => image,
+            Err(_) => return,
+        };
+        let captured = match image_codec::encode_captured_image(&image) {
+            Ok(captured) => captured,
+            Err(e) => {
+                eprintln!("处理剪切板图片失败: {}", e);
+                return;
+            }
+        };
+        
+        let hash = blob_store::content_hash(&captured.png);
+        if self.last_image_hash.as_deref() == Some(hash.as_str()) {
+            return;
+        }
+        self.last_image_hash = Some(hash.clone());
+        
+        if let Ok(rgba) = image.to_rgba8() {
+            let fingerprint = loopback::image_fingerprint(rgba.as_raw(), captured.width, captured.height);
+            if self.handle_loopback(&fingerprint, |item| item.image.as_ref().is_some_and(|image| image.hash == hash)) {
+                return;
+            }
+        }
+        
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        
+        let existing = match self.storage.lock() {
+            Ok(storage_lock) => {
+                // 相同图片已存在时只更新时间，不重复保存
+                let existing = storage_lock.find_by_content_hash(&hash);
+                if existing.is_none() {
+                    if let Err(e) = storage_lock.blobs().put_with_hash(&hash, &captured.png) {
+                        eprintln!("保存图片数据失败: {}", e);
+                        return;
+                    }
+                }
+                existing
+            }
+            Err(_) => return,
+        };
+        
+        let item = match existing {
+            Some(mut item) => {
+                item.timestamp = timestamp;
+                item
+            }
+            None => ClipboardItem {
+                id: uuid::Uuid::new_v4().to_string(),
+                content: format!("图片 {}×{}", captured.width, captured.height),
+                timestamp,
+                item_type: "image".to_string(),
+                size: Some(captured.png.len() as u64),
+                source_app,
+                image: Some(ImageInfo {
+                    hash,
+                    width: captured.width,
+                    height: captured.height,
+                    thumbnail: captured.thumbnail,
+                }),
+                ..Default::default()
+            },
+        };
+        
+        self.add_item_to_history(item);
+    }
 }
 
 impl ClipboardHandler for ClipboardManager {
     fn on_clipboard_change(&mut self) {
-        // 简化逻辑：只区分文件和文本
+        // 获取复制来源应用，并按设置过滤
+        let window: Option<WindowInfo> = self.window_provider.active_window();
+        let should_capture = self.settings.lock()
+            .map(|settings| settings.app_filter.should_capture(window.as_ref()))
+            .unwrap_or(true);
+        if !should_capture {
+            tracing::debug!("来源应用 {:?} 被过滤，跳过记录", window.as_ref().and_then(|w| w.display_name()));
+            self.mark_current_as_seen();
+            return;
+        }
+        let source_app = window.and_then(|w| w.display_name());
+
+        // 按文件、文本、图片的优先级检查
         
         // 首先检查是否有文件
         if self.ctx.has(ContentFormat::Files) {
-            self.check_files_change();
+            self.check_files_change(source_app);
             return;
         }
         
         // 然后检查文本
         if self.ctx.has(ContentFormat::Text) {
-            self.check_text_change();
+            self.check_text_change(source_app);
+            return;
+        }
+        
+        // 最后检查图片（例如截图）
+        if self.ctx.has(ContentFormat::Image) {
+            self.check_image_change(source_app);
         }
     }
 }
 
-// 启动剪贴板监听器
-fn start_clipboard_monitor(app_handle: AppHandle, storage: ClipboardStorage, sync_engine_container: Arc<Mutex<Option<ClipboardSync>>>) {
-    thread::spawn(move || {
-        let rt = tokio::runtime::Runtime::new().unwrap();
-        
-        // 先异步初始化同步引擎
-        let sync_engine = rt.block_on(async {
-            create_sync_engine_if_configured().await
-        });
-        
-        // 更新同步引擎容器
-        if let Some(ref engine) = sync_engine {
-            if let Ok(mut container) = sync_engine_container.lock() {
-                *container = Some(engine.clone());
-                tracing::info!("同步引擎初始化成功");
-            }
+// 剪贴板监听控制器，负责启动和停止监听线程
+struct ClipboardMonitor {
+    app_handle: AppHandle,
+    storage: ClipboardStorage,
+    sync_engine: ClipboardSyncContainer,
+    settings: SharedSettings,
+    writes: ClipboardWrites,
+    rules: SharedRules,
+    shutdown: Option<WatcherShutdown>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl ClipboardMonitor {
+    fn new(
+        app_handle: AppHandle,
+        storage: ClipboardStorage,
+        sync_engine: ClipboardSyncContainer,
+        settings: SharedSettings,
+        writes: ClipboardWrites,
+        rules: SharedRules,
+    ) -> Self {
+        Self {
+            app_handle,
+            storage,
+            sync_engine,
+            settings,
+            writes,
+            rules,
+            shutdown: None,
+            thread: None,
         }
+    }
+
+    fn is_running(&self) -> bool {
+        self.thread.as_ref().is_some_and(|thread| !thread.is_finished())
+    }
+
+    // 启动监听线程，已在运行时不做任何事
+    fn start(&mut self) -> Result<(), String> {
+        if self.is_running() {
+            return Ok(());
+        }
+        self.stop();
         
-        let manager = match ClipboardManager::new(app_handle.clone(), storage, sync_engine, rt.handle().clone()) {
-            Ok(manager) => manager,
-            Err(e) => {
-                eprintln!("Failed to create clipboard manager: {}", e);
-                return;
-            }
-        };
+        let app_handle = self.app_handle.clone();
+        let storage = self.storage.clone();
+        let sync_engine = self.sync_engine.clone();
+        let settings = self.settings.clone();
+        let writes = self.writes.clone();
+        let rules = self.rules.clone();
+        let (ready_tx, ready_rx) = std::sync::mpsc::channel();
+        
+        let thread = thread::spawn(move || {
+            // 每次启动监听时重新读取自定义文件类型，修改 file_types.json 后重启监听即可生效
+            let file_types = load_file_type_registry();
+            let manager = match ClipboardManager::new(
+                HistoryRecorder { app_handle, storage, sync_engine },
+                settings,
+                Box::new(SystemWindowInfoProvider::new()),
+                writes,
+                Arc::new(file_types),
+                rules,
+            ) {
+                Ok(manager) => manager,
+                Err(e) => {
+                    let _ = ready_tx.send(Err(e));
+                    return;
+                }
+            };
 
-        let mut watcher = match ClipboardWatcherContext::new() {
-            Ok(watcher) => watcher,
-            Err(e) => {
-                eprintln!("Failed to create clipboard watcher: {}", e);
-                return;
-            }
-        };
+            let mut watcher = match ClipboardWatcherContext::new() {
+                Ok(watcher) => watcher,
+                Err(e) => {
+                    let _ = ready_tx.send(Err(format!("Failed to create clipboard watcher: {}", e)));
+                    return;
+                }
+            };
 
-        println!("开始监听剪切板变化...");
+            // 添加处理器，把停止通道交给控制器后开始监听
+            watcher.add_handler(manager);
+            let _ = ready_tx.send(Ok(watcher.get_shutdown_channel()));
+            
+            println!("开始监听剪切板变化...");
+            watcher.start_watch();
+            println!("剪切板监听已停止");
+        });
         
-        // 添加处理器并开始监听
-        watcher.add_handler(manager);
-        watcher.start_watch();
-    });
-}
+        let shutdown = ready_rx
+            .recv()
+            .map_err(|_| "剪切板监听线程启动失败".to_string())??;
+        self.shutdown = Some(shutdown);
+        self.thread = Some(thread);
+        Ok(())
+    }
 
-// 检测文件类型的辅助函数
-fn detect_file_type(file_path: &str) -> FileTypeInfo {
-    let path = Path::new(file_path);
-    let extension = path.extension()
-        .and_then(|ext| ext.to_str())
-        .unwrap_or("")
-        .to_lowercase();
-    
-    // 尝试从文件内容检测 MIME 类型
-    let mime_type = if let Ok(bytes) = std::fs::read(file_path) {
-        if let Some(kind) = infer::get(&bytes) {
-            kind.mime_type().to_string()
-        } else {
-            guess_mime_by_extension(&extension)
+    // 停止监听线程并等待其退出
+    fn stop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            shutdown.stop();
+        }
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                eprintln!("剪切板监听线程异常退出");
+            }
         }
-    } else {
-        guess_mime_by_extension(&extension)
-    };
-    
-    // 根据扩展名或 MIME 类型确定类别
-    let category = categorize_file(&extension, &mime_type);
-    
-    FileTypeInfo {
-        path: file_path.to_string(),
-        file_type: extension,
-        mime_type,
-        category,
-    }
-}
-
-// 根据扩展名猜测 MIME 类型
-fn guess_mime_by_extension(extension: &str) -> String {
-    match extension {
-        "txt" => "text/plain",
-        "pdf" => "application/pdf",
-        "doc" | "docx" => "application/msword",
-        "xls" | "xlsx" => "application/vnd.ms-excel",
-        "ppt" | "pptx" => "application/vnd.ms-powerpoint",
-        "zip" => "application/zip",
-        "rar" => "application/x-rar-compressed",
-        "7z" => "application/x-7z-compressed",
-        "jpg" | "jpeg" => "image/jpeg",
-        "png" => "image/png",
-        "gif" => "image/gif",
-        "mp4" => "video/mp4",
-        "mp3" => "audio/mpeg",
-        "wav" => "audio/wav",
-        "html" | "htm" => "text/html",
-        "css" => "text/css",
-        "js" => "application/javascript",
-        "json" => "application/json",
-        "xml" => "application/xml",
-        _ => "application/octet-stream",
-    }.to_string()
-}
-
-// 文件分类
-fn categorize_file(extension: &str, mime_type: &str) -> String {
-    if mime_type.starts_with("image/") {
-        "image".to_string()
-    } else if mime_type.starts_with("video/") {
-        "video".to_string()
-    } else if mime_type.starts_with("audio/") {
-        "audio".to_string()
-    } else if mime_type.starts_with("text/") || matches!(extension, "txt" | "md" | "csv" | "log") {
-        "text".to_string()
-    } else if matches!(extension, "pdf" | "doc" | "docx" | "xls" | "xlsx" | "ppt" | "pptx") {
-        "document".to_string()
-    } else if matches!(extension, "zip" | "rar" | "7z" | "tar" | "gz" | "bz2") {
-        "archive".to_string()
-    } else if matches!(extension, "js" | "ts" | "py" | "java" | "cpp" | "c" | "h" | "rs" | "go" | "php" | "rb" | "swift") {
-        "code".to_string()
-    } else {
-        "other".to_string()
     }
 }
 
+// 托盘菜单中暂停/恢复记录的菜单项，记录状态变化时更新文字
+struct CaptureToggleMenuItem(MenuItem<tauri::Wry>);
+
 /// 获取应用配置目录，跨平台适配
 fn get_app_data_dir() -> PathBuf {
     // 尝试获取用户配置目录
@@ -527,10 +1635,47 @@ fn get_app_data_dir() -> PathBuf {
     }
 }
 
+// 通过自动化接口让已运行的实例显示主窗口；持有锁的是命令行工具时连接失败，只记录日志
+#[cfg(unix)]
+fn focus_running_instance() {
+    let runtime = match tokio::runtime::Builder::new_current_thread().enable_all().build() {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            tracing::warn!("无法通知已运行的实例: {}", e);
+            return;
+        }
+    };
+    let result = runtime.block_on(async {
+        let mut client = ipc::IpcClient::connect(&get_app_data_dir()).await?;
+        client.call("show_window", serde_json::Value::Null).await
+    });
+    if let Err(e) = result {
+        tracing::warn!("无法显示已运行实例的窗口: {:#}", e);
+    }
+}
+
+#[cfg(not(unix))]
+fn focus_running_instance() {}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     // 初始化日志
     tracing_subscriber::fmt::init();
+
+    // 运行期间持有应用数据目录的锁，命令行工具据此避免同时写入存储；
+    // 已有实例运行时显示它的窗口后退出，不能让两个进程写入同一份历史和发件箱
+    let _instance_lock = match InstanceLock::acquire_with_timeout(&get_app_data_dir(), std::time::Duration::from_secs(5)) {
+        Ok(Some(lock)) => lock,
+        Ok(None) => {
+            eprintln!("Clippy 已在运行（或命令行工具正在修改历史），本次启动退出");
+            focus_running_instance();
+            std::process::exit(0);
+        }
+        Err(e) => {
+            eprintln!("获取应用数据目录锁失败: {}", e);
+            std::process::exit(1);
+        }
+    };
     
     // 创建存储引擎 - 使用用户配置目录而不是项目目录
     let storage_dir = get_app_data_dir();
@@ -545,22 +1690,77 @@ pub fn run() {
     
     let clipboard_storage: ClipboardStorage = Arc::new(Mutex::new(storage_engine));
 
+    // 自身写入剪贴板的登记表，命令层和监听线程共享
+    let clipboard_writes: ClipboardWrites = Arc::new(WriteRegistry::default());
+
+    // 加载记录状态（暂停、无痕模式）
+    let capture_state: SharedCaptureState = Arc::new(Mutex::new(
+        CaptureState::load_or_default(&get_app_data_dir().join("capture_state.json"))
+    ));
+
+    // 加载应用设置
+    let app_settings: SharedSettings = Arc::new(Mutex::new(
+        AppSettings::load_or_default(&get_app_data_dir().join("settings.json"))
+    ));
+
+    // 加载片段库
+    let snippets: SharedSnippets = Arc::new(Mutex::new(
+        SnippetStore::load(get_app_data_dir().join("snippets.json"))
+    ));
+
+    // 加载粘贴队列
+    let paste_queue: SharedPasteQueue = Arc::new(Mutex::new(
+        PasteQueue::load_or_default(&get_app_data_dir().join("paste_queue.json"))
+    ));
+
     // 创建同步引擎的状态容器
     let sync_engine: Arc<Mutex<Option<ClipboardSync>>> = Arc::new(Mutex::new(None));
 
+    // 规则文件修改后自动重新加载，不需要重启监听
+    let rules: SharedRules = Arc::new(Mutex::new(RuleEngine::load(get_app_data_dir().join("rules.json"))));
+
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_shell::init())
         .manage(clipboard_storage.clone())
         .manage(sync_engine.clone())
+        .manage(app_settings.clone())
+        .manage(clipboard_writes.clone())
+        .manage(snippets)
+        .manage(paste_queue)
+        .manage(capture_state)
         .setup(move |app| {
+            // 创建托盘菜单：暂停/恢复记录、无痕模式
+            let toggle_item = MenuItem::with_id(app, "toggle_capture", "暂停记录", true, None::<&str>)?;
+            let incognito_item = MenuItem::with_id(app, "incognito", "无痕模式 15 分钟", true, None::<&str>)?;
+            let tray_menu = Menu::with_items(app, &[&toggle_item, &incognito_item])?;
+            app.manage(CaptureToggleMenuItem(toggle_item));
+
             // 创建系统托盘
             let _tray = TrayIconBuilder::new()
                 .icon(app.default_window_icon().unwrap().clone())
                 .title("Clippy - 剪贴板管理器")
                 .tooltip("Clippy - 剪贴板管理器")
+                .menu(&tray_menu)
+                .show_menu_on_left_click(false)
+                .on_menu_event(|app_handle, event| {
+                    let result = match event.id.as_ref() {
+                        "toggle_capture" => {
+                            if current_capture_status(app_handle).paused {
+                                update_capture_state(app_handle, CaptureState::default())
+                            } else {
+                                update_capture_state(app_handle, CaptureState::paused())
+                            }
+                        }
+                        "incognito" => update_capture_state(app_handle, CaptureState::incognito(unix_now(), 15)),
+                        _ => return,
+                    };
+                    if let Err(e) = result {
+                        eprintln!("切换记录状态失败: {}", e);
+                    }
+                })
                 .on_tray_icon_event(|tray, event| {
-                    if let TrayIconEvent::Click { .. } = event {
+                    if let TrayIconEvent::Click { button: MouseButton::Left, button_state: MouseButtonState::Up, .. } = event {
                         if let Some(window) = tray.app_handle().get_webview_window("main") {
                             let _ = window.show();
                             let _ = window.set_focus();
@@ -569,8 +1769,43 @@ pub fn run() {
                 })
                 .build(app)?;
 
-            // 启动剪贴板监听器（带同步引擎初始化）
-            start_clipboard_monitor(app.handle().clone(), clipboard_storage, sync_engine);
+            // 后台初始化同步引擎；初始化期间已经通过 setup_sync 等命令创建了引擎时保留该引擎
+            let sync_engine_container = sync_engine.clone();
+            let app_handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                let result = install_sync_engine(&sync_engine_container, false, || {
+                    create_sync_engine_if_configured(&app_handle)
+                })
+                .await;
+                match result {
+                    Ok(()) if sync_engine_container.lock().is_ok_and(|container| container.is_some()) => {
+                        tracing::info!("同步引擎初始化成功");
+                    }
+                    Ok(()) => {}
+                    Err(e) => tracing::warn!("初始化同步引擎失败: {}", e),
+                }
+            });
+
+            // 创建剪贴板监听控制器，并按保存的记录状态启动监听
+            let monitor: SharedMonitor = Arc::new(Mutex::new(ClipboardMonitor::new(
+                app.handle().clone(),
+                clipboard_storage,
+                sync_engine,
+                app_settings,
+                clipboard_writes,
+                rules,
+            )));
+            app.manage(monitor);
+            if let Err(e) = apply_capture_state(app.handle()) {
+                eprintln!("启动剪切板监听失败: {}", e);
+            }
+
+            // 定期删除规则设置了到期时间的项目
+            start_expiry_sweeper(app.handle().clone());
+
+            // 启动本地自动化接口，供脚本和编辑器插件使用
+            #[cfg(unix)]
+            start_ipc_server(app.handle());
 
             Ok(())
         })
@@ -580,9 +1815,38 @@ pub fn run() {
             delete_clipboard_item,
             get_storage_stats,
             compact_storage,
+            get_app_filter,
+            set_app_filter,
+            get_file_snapshot_settings,
+            set_file_snapshot_settings,
+            get_transforms,
+            get_transform_chains,
+            save_transform_chain,
+            delete_transform_chain,
+            transform_text,
+            get_snippets,
+            save_snippet,
+            delete_snippet,
+            get_snippet_inputs,
+            render_snippet,
+            import_snippets,
+            export_snippets,
+            sync_snippets,
+            get_paste_queue,
+            start_paste_queue,
+            stop_paste_queue,
+            paste_queue_next,
+            merge_clipboard_items,
             copy_to_clipboard,
             copy_image_to_clipboard,
             copy_files_to_clipboard,
+            copy_clipboard_item,
+            copy_item_as_plain_text,
+            get_capture_state,
+            pause_capture,
+            resume_capture,
+            start_incognito,
+            restart_clipboard_monitor,
             setup_sync,
             sync_now,
             get_sync_status,
@@ -596,39 +1860,63 @@ pub fn run() {
 }
 
 /// 如果存在配置，创建同步引擎
-async fn create_sync_engine_if_configured() -> Option<ClipboardSync> {
+async fn create_sync_engine_if_configured(app_handle: &AppHandle) -> Option<ClipboardSync> {
+    let sync_engine = create_sync_engine().await?;
+
+    // 其他设备的变化写入本地历史并通知前端，需要在第一次同步之前设置
+    let app_handle = app_handle.clone();
+    sync_engine.on_remote_changes(move |delta| {
+        let storage = app_handle.state::<ClipboardStorage>();
+        let changes = apply_remote_changes(&mut storage.lock().unwrap(), &delta);
+        if !changes.is_empty() {
+            let _ = app_handle.emit("sync-items-changed", &changes);
+        }
+    });
+
+    // 启动后台同步任务
+    let sync_engine_clone = sync_engine.clone();
+    tokio::spawn(async move {
+        if let Err(e) = sync_engine_clone.start_background_sync().await {
+            tracing::error!("后台同步任务失败: {}", e);
+        }
+    });
+
+    Some(sync_engine)
+}
+
+// 按已保存的存储配置创建同步引擎，不启动后台同步；未配置存储时返回 None
+async fn create_sync_engine() -> Option<ClipboardSync> {
     // 尝试从配置文件加载同步配置
     let config_file = get_app_data_dir().join("storage_config.json");
-    if let Ok(storage_config) = StorageConfig::load_from_file(config_file.to_string_lossy().as_ref()) {
-        if let Ok(operator) = storage_config.create_operator().await {
-            // 生成设备ID（应该持久化存储）
-            let device_id = get_or_create_device_id();
-            
-            // 这里应该从用户配置获取user_id，暂时使用默认值
-            let user_id = std::env::var("CLIPPY_USER_ID").unwrap_or_else(|_| "default_user".to_string());
-            
-            let sync_config = SyncConfig {
-                user_id,
-                device_id,
-                storage_operator: operator,
-                sync_interval_seconds: 15, // 15秒同步一次
-            };
-            
-            let sync_engine = Arc::new(SyncEngine::new(sync_config));
-            
-            // 启动后台同步任务
-            let sync_engine_clone = sync_engine.clone();
-            tokio::spawn(async move {
-                if let Err(e) = sync_engine_clone.start_background_sync().await {
-                    tracing::error!("后台同步任务失败: {}", e);
-                }
-            });
-            
-            return Some(sync_engine);
-        }
-    }
-    
-    None
+    let storage_config = StorageConfig::load_from_file(config_file.to_string_lossy().as_ref()).ok()?;
+    let operator = storage_config.create_operator().await.ok()?;
+
+    // 生成设备ID（应该持久化存储）
+    let device_id = get_or_create_device_id();
+
+    // 这里应该从用户配置获取user_id，暂时使用默认值
+    let user_id = std::env::var("CLIPPY_USER_ID").unwrap_or_else(|_| "default_user".to_string());
+
+    let sync_config = SyncConfig {
+        user_id,
+        device_id,
+        storage_operator: operator,
+        sync_interval_seconds: 15, // 15秒同步一次
+        retry_attempts: storage_config.retry_attempts,
+        timeout_seconds: storage_config.timeout_seconds,
+        outbox_path: get_app_data_dir().join(sync_outbox::OUTBOX_FILE_NAME),
+        clock_path: get_app_data_dir().join("sync_clock.json"),
+        state_path: get_app_data_dir().join("sync_state.json"),
+        segment_max_ops: 100,
+        segment_window_seconds: 2, // 连续复制的内容合并上传，减少对象存储请求
+        snapshot_every_ops: 500,
+        snapshot_interval_seconds: 6 * 3600,
+        snapshot_keep: 3,
+        oplog_retention_seconds: 7 * 24 * 3600, // 离线不超过一周的设备仍能拉取全部操作
+        blobs: BlobStore::new(get_app_data_dir().join("blobs")).ok()?,
+    };
+
+    Some(Arc::new(SyncEngine::new(sync_config)))
 }
 
 /// 获取或创建设备唯一ID
@@ -658,3 +1946,171 @@ fn get_or_create_device_id() -> String {
     
     device_id
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sync::tests::{device_segments, memory_engine, memory_operator, runtime};
+    use sync::{OpType, Operation};
+
+    fn text_item(id: &str, expires_at: Option<u64>) -> ClipboardItem {
+        ClipboardItem {
+            id: id.to_string(),
+            item_type: "text".to_string(),
+            content: format!("content of {}", id),
+            timestamp: 1_700_000_000,
+            expires_at,
+            ..Default::default()
+        }
+    }
+
+    // 两个设备共享内存存储：设备 A 复制三个项目，设备 B 同步后把变化写入自己的历史
+    struct TwoDevices {
+        _dir: tempfile::TempDir,
+        operator: opendal::Operator,
+        local: StorageEngine,
+        engine: SyncEngine,
+        remote_storage: ClipboardStorage,
+        remote_engine: SyncEngine,
+    }
+
+    async fn two_devices() -> TwoDevices {
+        let dir = tempfile::tempdir().unwrap();
+        let operator = memory_operator();
+        let mut local = StorageEngine::new(dir.path().join("history-a")).unwrap();
+        let engine = memory_engine(dir.path(), &operator, "device-a");
+
+        let remote_storage: ClipboardStorage = Arc::new(Mutex::new(StorageEngine::new(dir.path().join("history-b")).unwrap()));
+        let remote_engine = memory_engine(dir.path(), &operator, "device-b");
+        let storage = remote_storage.clone();
+        remote_engine.on_remote_changes(move |delta| {
+            apply_remote_changes(&mut storage.lock().unwrap(), &delta);
+        });
+
+        for item in [text_item("a", None), text_item("b", Some(100)), text_item("c", Some(200))] {
+            local.insert(&item).unwrap();
+            engine.local_add(SyncClipboardItem::from(&item), Vec::new()).await.unwrap();
+        }
+        remote_engine.sync().await.unwrap();
+        assert_eq!(remote_storage.lock().unwrap().get_all().len(), 3);
+
+        TwoDevices { _dir: dir, operator, local, engine, remote_storage, remote_engine }
+    }
+
+    impl TwoDevices {
+        // 删除后设备 A 新上传的段
+        async fn push_deletes(&self, removed: &[ClipboardItem]) -> Vec<Operation> {
+            let before = device_segments(&self.operator, "device-a").await.len();
+            push_local_deletes(&self.engine, removed).await.unwrap();
+            let mut segments = device_segments(&self.operator, "device-a").await;
+            assert_eq!(segments.len(), before + 1, "应只上传一个段");
+            segments.pop().unwrap()
+        }
+
+        async fn remote_ids(&self) -> Vec<String> {
+            self.remote_engine.sync().await.unwrap();
+            let mut ids: Vec<String> = self.remote_storage.lock().unwrap().get_all().into_iter().map(|item| item.id).collect();
+            ids.sort();
+            ids
+        }
+    }
+
+    #[test]
+    fn single_delete_uploads_one_delete_operation() {
+        runtime().block_on(async {
+            let mut devices = two_devices().await;
+            let item = devices.local.get("b").unwrap();
+            devices.local.delete("b").unwrap();
+
+            let segment = devices.push_deletes(&[item]).await;
+            assert_eq!(segment.len(), 1);
+            assert_eq!(segment[0].op_type, OpType::Delete);
+            assert_eq!(segment[0].target_id, "b");
+            assert_eq!(devices.remote_ids().await, vec!["a", "c"]);
+        });
+    }
+
+    #[test]
+    fn clear_all_uploads_one_batch_operation() {
+        runtime().block_on(async {
+            let mut devices = two_devices().await;
+            let removed = devices.local.clear_all().unwrap();
+            assert_eq!(removed.len(), 3);
+
+            let segment = devices.push_deletes(&removed).await;
+            assert_eq!(segment.len(), 1);
+            assert_eq!(segment[0].op_type, OpType::DeleteBatch);
+            assert_eq!(segment[0].target_ids.len(), 3);
+            assert!(devices.remote_ids().await.is_empty());
+        });
+    }
+
+    #[test]
+    fn expired_items_upload_one_batch_operation() {
+        runtime().block_on(async {
+            let mut devices = two_devices().await;
+            let expired = devices.local.remove_expired(300).unwrap();
+            assert_eq!(expired.len(), 2);
+
+            let segment = devices.push_deletes(&expired).await;
+            assert_eq!(segment.len(), 1);
+            assert_eq!(segment[0].op_type, OpType::DeleteBatch);
+            assert_eq!(devices.remote_ids().await, vec!["a"]);
+        });
+    }
+
+    #[test]
+    fn local_only_items_are_not_synced() {
+        runtime().block_on(async {
+            let devices = two_devices().await;
+            let item = ClipboardItem { exclude_from_sync: true, ..text_item("secret", None) };
+
+            push_local_deletes(&devices.engine, &[item]).await.unwrap();
+            assert_eq!(device_segments(&devices.operator, "device-a").await.len(), 3);
+        });
+    }
+
+    #[test]
+    fn concurrent_installs_leave_one_running_engine() {
+        let dir = tempfile::tempdir().unwrap();
+        let operator = memory_operator();
+        runtime().block_on(async {
+            let container: ClipboardSyncContainer = Arc::new(Mutex::new(None));
+            let created: Arc<Mutex<Vec<ClipboardSync>>> = Arc::default();
+            // 创建引擎需要一段时间，两次安装交错执行
+            let install = |replace: bool| {
+                let container = container.clone();
+                let created = created.clone();
+                let dir = dir.path().to_path_buf();
+                let operator = operator.clone();
+                tokio::spawn(async move {
+                    install_sync_engine(&container, replace, || async {
+                        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                        let engine = Arc::new(memory_engine(&dir, &operator, "device-a"));
+                        created.lock().unwrap().push(engine.clone());
+                        Some(engine)
+                    })
+                    .await
+                })
+            };
+
+            let first = install(true);
+            let second = install(true);
+            first.await.unwrap().unwrap();
+            second.await.unwrap().unwrap();
+
+            let engines = created.lock().unwrap().clone();
+            assert_eq!(engines.len(), 2);
+            let installed = container.lock().unwrap().clone().unwrap();
+            assert!(Arc::ptr_eq(&installed, &engines[1]));
+            // 被替换的引擎已经停止，不再接受本地操作
+            assert!(engines[0].local_delete("a".to_string()).await.is_err());
+            installed.local_delete("a".to_string()).await.unwrap();
+
+            // 启动时的初始化保留已经安装的引擎
+            install(false).await.unwrap().unwrap();
+            assert_eq!(created.lock().unwrap().len(), 2);
+            assert!(Arc::ptr_eq(&container.lock().unwrap().clone().unwrap(), &installed));
+        });
+    }
+}
@@ -0,0 +1,76 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::blob_store::content_hash;
+
+/// 写入令牌的默认有效期，超过后不再认为剪贴板变化是 Clippy 自己触发的
+pub const DEFAULT_WRITE_TTL: Duration = Duration::from_secs(5);
+
+/// 文本内容的指纹
+pub fn text_fingerprint(text: &str) -> String {
+    format!("text:{}", content_hash(text.as_bytes()))
+}
+
+/// 文件列表的指纹
+pub fn files_fingerprint(files: &[String]) -> String {
+    format!("files:{}", content_hash(files.join("\n").as_bytes()))
+}
+
+/// 图片的指纹，使用像素数据计算，与编码格式无关
+pub fn image_fingerprint(rgba: &[u8], width: u32, height: u32) -> String {
+    format!("image:{}x{}:{}", width, height, content_hash(rgba))
+}
+
+struct WriteToken {
+    fingerprint: String,
+    item_id: Option<String>,
+    expires_at: Instant,
+}
+
+/// 记录 Clippy 自己写入剪贴板的内容
+///
+/// 命令层写入剪贴板前登记内容指纹，监听线程收到变化时先查询这里，
+/// 命中则说明是自己写入引起的，不再当作新内容记录。
+pub struct WriteRegistry {
+    ttl: Duration,
+    tokens: Mutex<Vec<WriteToken>>,
+}
+
+impl WriteRegistry {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            tokens: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// 登记一次写入；item_id 为写入内容对应的历史项目（如果有）
+    pub fn register(&self, fingerprint: String, item_id: Option<String>) {
+        let mut tokens = self.tokens.lock().unwrap();
+        let now = Instant::now();
+        tokens.retain(|token| token.expires_at > now && token.fingerprint != fingerprint);
+        tokens.push(WriteToken {
+            fingerprint,
+            item_id,
+            expires_at: now + self.ttl,
+        });
+    }
+
+    /// 查询并消费与指纹匹配的写入令牌
+    ///
+    /// 返回 None 表示不是自己写入的；返回 Some(item_id) 表示命中，item_id 为登记时关联的历史项目。
+    pub fn take(&self, fingerprint: &str) -> Option<Option<String>> {
+        let mut tokens = self.tokens.lock().unwrap();
+        let now = Instant::now();
+        tokens.retain(|token| token.expires_at > now);
+
+        let index = tokens.iter().position(|token| token.fingerprint == fingerprint)?;
+        Some(tokens.remove(index).item_id)
+    }
+}
+
+impl Default for WriteRegistry {
+    fn default() -> Self {
+        Self::new(DEFAULT_WRITE_TTL)
+    }
+}
@@ -0,0 +1,110 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::Path;
+
+/// 粘贴队列的出队顺序
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QueueOrder {
+    /// 先复制的先粘贴
+    #[default]
+    Fifo,
+    /// 后复制的先粘贴
+    Lifo,
+}
+
+/// 粘贴队列，保存在应用数据目录的 paste_queue.json 中，重启后继续
+///
+/// 开启后新记录的项目依次加入队列，每次前进把下一个项目写入剪贴板。
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PasteQueue {
+    pub active: bool,
+    pub order: QueueOrder,
+    /// 队列中的历史项目 id，按复制顺序排列
+    pub items: VecDeque<String>,
+    /// 已经出队的项目数
+    #[serde(default)]
+    pub position: usize,
+}
+
+impl PasteQueue {
+    /// 开启队列，清空之前的内容
+    pub fn start(order: QueueOrder) -> Self {
+        Self {
+            active: true,
+            order,
+            ..Default::default()
+        }
+    }
+
+    /// 队列开启时加入项目，返回是否加入
+    pub fn push(&mut self, item_id: String) -> bool {
+        if !self.active {
+            return false;
+        }
+        self.items.push_back(item_id);
+        true
+    }
+
+    /// 取出下一个项目
+    pub fn next(&mut self) -> Option<String> {
+        let item_id = match self.order {
+            QueueOrder::Fifo => self.items.pop_front(),
+            QueueOrder::Lifo => self.items.pop_back(),
+        }?;
+        self.position += 1;
+        Some(item_id)
+    }
+
+    /// 下一个将要出队的项目
+    pub fn peek(&self) -> Option<&String> {
+        match self.order {
+            QueueOrder::Fifo => self.items.front(),
+            QueueOrder::Lifo => self.items.back(),
+        }
+    }
+
+    pub fn status(&self) -> PasteQueueStatus {
+        PasteQueueStatus {
+            active: self.active,
+            order: self.order,
+            position: self.position,
+            remaining: self.items.len(),
+            next_item_id: self.peek().cloned(),
+        }
+    }
+
+    /// 从文件加载队列，文件不存在或无法解析时为关闭状态
+    pub fn load_or_default(path: &Path) -> Self {
+        match std::fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                tracing::warn!("解析粘贴队列失败，已重置: {}", e);
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    /// 保存队列到文件
+    pub fn save_to_file(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let content = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, content).context("Failed to save paste queue")?;
+        Ok(())
+    }
+}
+
+/// 发送给前端的队列状态
+#[derive(Debug, Clone, Serialize)]
+pub struct PasteQueueStatus {
+    pub active: bool,
+    pub order: QueueOrder,
+    /// 已经粘贴的项目数
+    pub position: usize,
+    /// 剩余的项目数
+    pub remaining: usize,
+    pub next_item_id: Option<String>,
+}
@@ -0,0 +1,507 @@
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::storage::ClipboardItem;
+use crate::text_classify;
+use crate::text_transform::{self, Transform};
+
+/// 规则命令的最长运行时间，超时后结束进程
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 自动处理新记录项目的规则，所有条件都满足时按顺序执行动作
+///
+/// 规则保存在应用数据目录的 rules.json 中（规则数组），按文件中的顺序依次匹配。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rule {
+    pub name: String,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub when: RuleConditions,
+    pub actions: Vec<RuleAction>,
+    /// 匹配后不再检查后面的规则
+    #[serde(default)]
+    pub stop: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// 规则条件，未设置的条件视为满足
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RuleConditions {
+    /// 正则表达式，匹配文本内容或任一文件路径
+    pub pattern: Option<String>,
+    /// 项目类型：text、files 或 image
+    pub item_type: Option<String>,
+    /// 任一文件的类别，如 "image"、"document"
+    pub category: Option<String>,
+    /// 来源应用名称，不区分大小写
+    pub source_app: Option<String>,
+    /// 项目大小范围（字节）
+    pub min_size: Option<u64>,
+    pub max_size: Option<u64>,
+}
+
+/// 规则动作
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum RuleAction {
+    /// 添加标签
+    Tag { tag: String },
+    /// 置顶
+    Pin,
+    /// 不保存该项目，也不再执行后面的动作和规则
+    Skip,
+    /// 对文本内容依次执行转换
+    Transform { steps: Vec<Transform> },
+    /// 只保存在本机，不上传到同步存储
+    ExcludeFromSync,
+    /// 复制后经过指定分钟数自动删除
+    Expire { minutes: u64 },
+    /// 运行本地命令，项目内容通过标准输入传入
+    Command(RuleCommand),
+}
+
+/// 规则触发的本地命令，不经过 shell 直接执行
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleCommand {
+    pub program: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+/// 对一个项目执行规则的结果；标签、置顶等动作直接修改项目本身
+#[derive(Debug, Default)]
+pub struct RuleOutcome {
+    /// 是否跳过保存
+    pub skip: bool,
+    /// 需要运行的命令，按触发顺序排列
+    pub commands: Vec<RuleCommand>,
+    /// 匹配的规则名称
+    pub matched: Vec<String>,
+}
+
+// 预先编译好正则表达式的规则
+struct CompiledRule {
+    rule: Rule,
+    pattern: Option<Regex>,
+}
+
+/// 一组规则，不依赖剪贴板和文件，可以直接对构造的项目求值
+#[derive(Default)]
+pub struct RuleSet {
+    rules: Vec<CompiledRule>,
+}
+
+impl RuleSet {
+    /// 编译规则；任一正则表达式无效时返回错误
+    pub fn new(rules: Vec<Rule>) -> Result<Self> {
+        let rules = rules
+            .into_iter()
+            .map(|rule| {
+                let pattern = rule
+                    .when
+                    .pattern
+                    .as_deref()
+                    .map(Regex::new)
+                    .transpose()
+                    .with_context(|| format!("规则 {} 的正则表达式无效", rule.name))?;
+                Ok(CompiledRule { rule, pattern })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { rules })
+    }
+
+    /// 按顺序对项目执行匹配的规则
+    pub fn evaluate(&self, item: &mut ClipboardItem) -> RuleOutcome {
+        let mut outcome = RuleOutcome::default();
+
+        for compiled in self.rules.iter().filter(|compiled| compiled.rule.enabled) {
+            if !compiled.matches(item) {
+                continue;
+            }
+            outcome.matched.push(compiled.rule.name.clone());
+
+            for action in &compiled.rule.actions {
+                match action {
+                    RuleAction::Tag { tag } => {
+                        if !item.tags.contains(tag) {
+                            item.tags.push(tag.clone());
+                        }
+                    }
+                    RuleAction::Pin => item.pinned = true,
+                    RuleAction::Skip => {
+                        outcome.skip = true;
+                        return outcome;
+                    }
+                    RuleAction::Transform { steps } => transform_item(item, steps, &compiled.rule.name),
+                    RuleAction::ExcludeFromSync => item.exclude_from_sync = true,
+                    RuleAction::Expire { minutes } => {
+                        let expires_at = item.timestamp.saturating_add(minutes.saturating_mul(60));
+                        // 多条规则设置到期时间时以最早的为准
+                        item.expires_at = Some(item.expires_at.map_or(expires_at, |current| current.min(expires_at)));
+                    }
+                    RuleAction::Command(command) => outcome.commands.push(command.clone()),
+                }
+            }
+
+            if compiled.rule.stop {
+                break;
+            }
+        }
+        outcome
+    }
+}
+
+impl CompiledRule {
+    fn matches(&self, item: &ClipboardItem) -> bool {
+        let when = &self.rule.when;
+        let file_types = item.file_types.iter().flatten();
+        let size = item.size.unwrap_or(item.content.len() as u64);
+
+        when.item_type.as_ref().is_none_or(|item_type| item.item_type == *item_type)
+            && when.category.as_ref().is_none_or(|category| {
+                file_types.clone().any(|info| info.category.eq_ignore_ascii_case(category))
+            })
+            && when.source_app.as_ref().is_none_or(|app| {
+                item.source_app.as_ref().is_some_and(|source| source.eq_ignore_ascii_case(app.trim()))
+            })
+            && when.min_size.is_none_or(|min_size| size >= min_size)
+            && when.max_size.is_none_or(|max_size| size <= max_size)
+            && self.pattern.as_ref().is_none_or(|pattern| match item.item_type.as_str() {
+                "files" => item.file_paths.iter().flatten().any(|path| pattern.is_match(path)),
+                _ => pattern.is_match(&item.content),
+            })
+    }
+}
+
+// 只转换文本项目；转换后原来的富文本格式不再对应，一并去掉
+fn transform_item(item: &mut ClipboardItem, steps: &[Transform], rule_name: &str) {
+    if item.item_type != "text" {
+        return;
+    }
+    match text_transform::apply_chain(&item.content, steps) {
+        Ok(content) => {
+            if content != item.content {
+                item.size = Some(content.len() as u64);
+                item.detected_kind = text_classify::classify(&content);
+                item.html = None;
+                item.rtf = None;
+                item.content = content;
+            }
+        }
+        Err(e) => tracing::warn!("规则 {} 转换失败: {}", rule_name, e),
+    }
+}
+
+/// 规则引擎：从规则文件加载规则，文件修改后下次求值时自动重新加载
+pub struct RuleEngine {
+    path: PathBuf,
+    rules: RuleSet,
+    modified: Option<SystemTime>,
+}
+
+impl RuleEngine {
+    /// 加载规则文件，文件不存在时没有规则
+    pub fn load(path: PathBuf) -> Self {
+        let mut engine = Self {
+            path,
+            rules: RuleSet::default(),
+            modified: None,
+        };
+        engine.reload_if_changed();
+        engine
+    }
+
+    /// 检查规则文件的修改时间，有变化时重新加载；新规则无效时保留原来的规则
+    fn reload_if_changed(&mut self) {
+        let modified = std::fs::metadata(&self.path).and_then(|metadata| metadata.modified()).ok();
+        if modified == self.modified {
+            return;
+        }
+        self.modified = modified;
+
+        if modified.is_none() {
+            self.rules = RuleSet::default();
+            return;
+        }
+        match read_rules_file(&self.path).and_then(RuleSet::new) {
+            Ok(rules) => {
+                tracing::info!("已加载规则文件: {}", self.path.display());
+                self.rules = rules;
+            }
+            Err(e) => tracing::warn!("加载规则失败，继续使用原来的规则: {:#}", e),
+        }
+    }
+
+    /// 对新记录的项目执行规则
+    pub fn evaluate(&mut self, item: &mut ClipboardItem) -> RuleOutcome {
+        self.reload_if_changed();
+        self.rules.evaluate(item)
+    }
+}
+
+fn read_rules_file(path: &std::path::Path) -> Result<Vec<Rule>> {
+    let content = std::fs::read_to_string(path).context("Failed to read rules file")?;
+    serde_json::from_str(&content).context("Failed to parse rules")
+}
+
+/// 在后台线程中运行规则命令，项目内容写入标准输入
+///
+/// 文本项目传入文本内容，文件项目每行一个路径，图片项目传入描述文本；
+/// 项目 id 和类型通过 CLIPPY_ITEM_ID、CLIPPY_ITEM_TYPE 环境变量传入。
+pub fn spawn_command(command: RuleCommand, item: &ClipboardItem) {
+    let input = match item.item_type.as_str() {
+        "files" => item.file_paths.clone().unwrap_or_default().join("\n"),
+        _ => item.content.clone(),
+    };
+    let item_id = item.id.clone();
+    let item_type = item.item_type.clone();
+
+    std::thread::spawn(move || {
+        if let Err(e) = run_command(&command, input, &item_id, &item_type, COMMAND_TIMEOUT) {
+            tracing::warn!("规则命令 {} 执行失败: {:#}", command.program, e);
+        }
+    });
+}
+
+fn run_command(command: &RuleCommand, input: String, item_id: &str, item_type: &str, timeout: Duration) -> Result<()> {
+    let deadline = Instant::now() + timeout;
+    let mut child = Command::new(&command.program)
+        .args(&command.args)
+        .env("CLIPPY_ITEM_ID", item_id)
+        .env("CLIPPY_ITEM_TYPE", item_type)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .context("Failed to start command")?;
+
+    // 在单独的线程中写入标准输入：命令不读取标准输入时，超过管道缓冲区的内容会一直阻塞，
+    // 超时结束进程后写入失败，线程随之退出；写完后关闭标准输入
+    if let Some(mut stdin) = child.stdin.take() {
+        std::thread::spawn(move || {
+            let _ = stdin.write_all(input.as_bytes());
+        });
+    }
+
+    loop {
+        if let Some(status) = child.try_wait()? {
+            if !status.success() {
+                anyhow::bail!("命令退出状态: {}", status);
+            }
+            return Ok(());
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            anyhow::bail!("命令运行超时");
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::FileTypeInfo;
+
+    fn rules(json: &str) -> RuleSet {
+        RuleSet::new(serde_json::from_str(json).unwrap()).unwrap()
+    }
+
+    fn text_item(content: &str) -> ClipboardItem {
+        ClipboardItem {
+            id: "item".to_string(),
+            item_type: "text".to_string(),
+            content: content.to_string(),
+            timestamp: 1_000,
+            ..Default::default()
+        }
+    }
+
+    fn files_item(paths: &[&str], category: &str) -> ClipboardItem {
+        ClipboardItem {
+            id: "files".to_string(),
+            item_type: "files".to_string(),
+            file_paths: Some(paths.iter().map(|path| path.to_string()).collect()),
+            file_types: Some(
+                paths
+                    .iter()
+                    .map(|path| FileTypeInfo {
+                        path: path.to_string(),
+                        category: category.to_string(),
+                        ..Default::default()
+                    })
+                    .collect(),
+            ),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn conditions_must_all_match() {
+        let set = rules(
+            r#"[{ "name": "github", "when": { "pattern": "^https://github\\.com/", "source_app": "Firefox", "max_size": 100 },
+                  "actions": [{ "action": "tag", "tag": "link" }] }]"#,
+        );
+
+        let mut item = text_item("https://github.com/tauri-apps/tauri");
+        item.source_app = Some("firefox".to_string());
+        assert_eq!(set.evaluate(&mut item).matched, vec!["github"]);
+        assert_eq!(item.tags, vec!["link"]);
+
+        // 来源应用不同
+        let mut item = text_item("https://github.com/tauri-apps/tauri");
+        item.source_app = Some("Code".to_string());
+        assert!(set.evaluate(&mut item).matched.is_empty());
+
+        // 超过大小上限
+        let mut item = text_item(&format!("https://github.com/{}", "a".repeat(100)));
+        item.source_app = Some("Firefox".to_string());
+        assert!(set.evaluate(&mut item).matched.is_empty());
+        assert!(item.tags.is_empty());
+    }
+
+    #[test]
+    fn file_rules_match_paths_and_categories() {
+        let set = rules(
+            r#"[{ "name": "screenshots", "when": { "item_type": "files", "category": "IMAGE", "pattern": "Screenshot" },
+                  "actions": [{ "action": "pin" }] }]"#,
+        );
+
+        let mut item = files_item(&["/tmp/a.txt", "/home/me/Screenshot 1.png"], "image");
+        set.evaluate(&mut item);
+        assert!(item.pinned);
+
+        let mut item = files_item(&["/home/me/Screenshot.pdf"], "document");
+        set.evaluate(&mut item);
+        assert!(!item.pinned);
+
+        // 文本内容包含关键字，但类型不是文件
+        let mut item = text_item("Screenshot");
+        set.evaluate(&mut item);
+        assert!(!item.pinned);
+    }
+
+    #[test]
+    fn actions_modify_the_item() {
+        let set = rules(
+            r#"[
+                { "name": "clean", "actions": [
+                    { "action": "transform", "steps": ["trim", "lowercase"] },
+                    { "action": "tag", "tag": "clean" },
+                    { "action": "tag", "tag": "clean" },
+                    { "action": "exclude_from_sync" },
+                    { "action": "expire", "minutes": 10 },
+                    { "action": "command", "program": "notify-send", "args": ["copied"] }
+                ] },
+                { "name": "sooner", "actions": [{ "action": "expire", "minutes": 5 }] },
+                { "name": "later", "actions": [{ "action": "expire", "minutes": 60 }] }
+            ]"#,
+        );
+
+        let mut item = text_item("  Hello World  ");
+        item.html = Some("<b>Hello World</b>".to_string());
+        let outcome = set.evaluate(&mut item);
+
+        assert!(!outcome.skip);
+        assert_eq!(outcome.matched, vec!["clean", "sooner", "later"]);
+        assert_eq!(outcome.commands.len(), 1);
+        assert_eq!(outcome.commands[0].args, vec!["copied"]);
+        assert_eq!(item.content, "hello world");
+        assert_eq!(item.size, Some(11));
+        assert_eq!(item.html, None);
+        assert_eq!(item.tags, vec!["clean"]);
+        assert!(item.exclude_from_sync);
+        // 多条规则设置到期时间时以最早的为准
+        assert_eq!(item.expires_at, Some(1_000 + 5 * 60));
+    }
+
+    #[test]
+    fn skip_and_stop_end_evaluation() {
+        let set = rules(
+            r#"[
+                { "name": "disabled", "enabled": false, "actions": [{ "action": "skip" }] },
+                { "name": "secret", "when": { "pattern": "^sk-" }, "actions": [{ "action": "skip" }, { "action": "pin" }] },
+                { "name": "first", "stop": true, "actions": [{ "action": "tag", "tag": "first" }] },
+                { "name": "second", "actions": [{ "action": "tag", "tag": "second" }] }
+            ]"#,
+        );
+
+        let mut item = text_item("sk-123456");
+        let outcome = set.evaluate(&mut item);
+        assert!(outcome.skip);
+        assert_eq!(outcome.matched, vec!["secret"]);
+        assert!(!item.pinned);
+
+        let mut item = text_item("hello");
+        let outcome = set.evaluate(&mut item);
+        assert!(!outcome.skip);
+        assert_eq!(outcome.matched, vec!["first"]);
+        assert_eq!(item.tags, vec!["first"]);
+    }
+
+    #[test]
+    fn invalid_pattern_is_rejected() {
+        let rules: Vec<Rule> =
+            serde_json::from_str(r#"[{ "name": "broken", "when": { "pattern": "(" }, "actions": [] }]"#).unwrap();
+        let error = RuleSet::new(rules).err().expect("正则表达式无效时应返回错误");
+        assert!(error.to_string().contains("broken"));
+    }
+
+    #[test]
+    fn engine_keeps_previous_rules_when_file_becomes_invalid() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rules.json");
+        let mut engine = RuleEngine::load(path.clone());
+        assert!(engine.evaluate(&mut text_item("hello")).matched.is_empty());
+
+        std::fs::write(&path, r#"[{ "name": "all", "actions": [{ "action": "pin" }] }]"#).unwrap();
+        engine.modified = None;
+        assert_eq!(engine.evaluate(&mut text_item("hello")).matched, vec!["all"]);
+
+        std::fs::write(&path, "[{").unwrap();
+        engine.modified = None;
+        assert_eq!(engine.evaluate(&mut text_item("hello")).matched, vec!["all"]);
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(engine.evaluate(&mut text_item("hello")).matched.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn command_that_ignores_stdin_times_out() {
+        let command = RuleCommand {
+            program: "sleep".to_string(),
+            args: vec!["5".to_string()],
+        };
+        // 远大于管道缓冲区的输入，命令不读取时写入会一直阻塞
+        let input = "x".repeat(4 * 1024 * 1024);
+        let started = Instant::now();
+        let error = run_command(&command, input, "item", "text", Duration::from_millis(300)).unwrap_err();
+        assert!(error.to_string().contains("超时"));
+        assert!(started.elapsed() < Duration::from_secs(3));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn command_receives_item_on_stdin() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("out.txt");
+        let command = RuleCommand {
+            program: "sh".to_string(),
+            args: vec!["-c".to_string(), format!("cat > '{}'; test \"$CLIPPY_ITEM_ID\" = item", output.display())],
+        };
+        run_command(&command, "hello".to_string(), "item", "text", Duration::from_secs(5)).unwrap();
+        assert_eq!(std::fs::read_to_string(output).unwrap(), "hello");
+    }
+}
@@ -0,0 +1,85 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::source_app::AppFilter;
+use crate::text_transform::TransformChain;
+
+/// 应用设置，保存在应用数据目录的 settings.json 中
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AppSettings {
+    /// 按来源应用过滤剪贴板记录
+    #[serde(default)]
+    pub app_filter: AppFilter,
+    /// 复制文件时是否保留副本
+    #[serde(default)]
+    pub file_snapshots: FileSnapshotSettings,
+    /// 保存的文本转换链
+    #[serde(default)]
+    pub transform_chains: Vec<TransformChain>,
+}
+
+/// 文件副本设置
+///
+/// 开启后复制文件时把文件内容保存到本地 blob 存储，原文件被移动或删除后仍可从历史中粘贴。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileSnapshotSettings {
+    #[serde(default)]
+    pub enabled: bool,
+    /// 单个文件的大小上限（字节），超过的文件不保留副本
+    #[serde(default = "default_max_file_size")]
+    pub max_file_size: u64,
+    /// 一次复制保留副本的总大小上限（字节）
+    #[serde(default = "default_max_total_size")]
+    pub max_total_size: u64,
+}
+
+fn default_max_file_size() -> u64 {
+    20 * 1024 * 1024
+}
+
+fn default_max_total_size() -> u64 {
+    100 * 1024 * 1024
+}
+
+impl Default for FileSnapshotSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_file_size: default_max_file_size(),
+            max_total_size: default_max_total_size(),
+        }
+    }
+}
+
+impl AppSettings {
+    /// 从文件加载设置，文件不存在或无法解析时使用默认设置
+    pub fn load_or_default(path: &Path) -> Self {
+        match Self::load_from_file(path) {
+            Ok(settings) => settings,
+            Err(e) => {
+                if path.exists() {
+                    tracing::warn!("加载设置失败，使用默认设置: {}", e);
+                }
+                Self::default()
+            }
+        }
+    }
+
+    /// 从文件加载设置
+    pub fn load_from_file(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path).context("Failed to read settings file")?;
+        let settings: Self = serde_json::from_str(&content).context("Failed to parse settings")?;
+        Ok(settings)
+    }
+
+    /// 保存设置到文件
+    pub fn save_to_file(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let content = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, content).context("Failed to save settings file")?;
+        Ok(())
+    }
+}
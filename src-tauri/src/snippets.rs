@@ -0,0 +1,291 @@
+use anyhow::{bail, Context, Result};
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// 光标位置标记，渲染时去掉并记录位置
+const CURSOR_MARKER: &str = "cursor";
+
+/// 片段模板
+///
+/// 模板中可以使用以下占位符：
+/// - `{{date}}`、`{{time}}`、`{{datetime}}`，或 `{{date:%Y/%m/%d}}` 指定格式
+/// - `{{clipboard}}`：当前剪贴板文本
+/// - `{{uuid}}`：新生成的 UUID
+/// - `{{cursor}}`：粘贴后光标所在位置
+/// - `{{input:名称}}`：渲染时由用户输入
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snippet {
+    pub id: String,
+    pub name: String,
+    pub template: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    pub updated_at: u64,
+    /// 已删除的片段保留为墓碑，用于同步时删除其他设备上的副本
+    #[serde(default)]
+    pub deleted: bool,
+}
+
+/// 渲染结果
+#[derive(Debug, Clone, Serialize)]
+pub struct RenderedSnippet {
+    pub text: String,
+    /// 光标位置（字符偏移），模板中没有 {{cursor}} 时为 None
+    pub cursor: Option<usize>,
+}
+
+/// 渲染时需要的外部数据
+pub struct RenderContext<'a> {
+    pub clipboard: Option<&'a str>,
+    pub inputs: &'a HashMap<String, String>,
+}
+
+// 模板中的一个片段：普通文本或占位符
+enum Part<'a> {
+    Text(&'a str),
+    Placeholder(&'a str),
+}
+
+fn parse(template: &str) -> Vec<Part<'_>> {
+    let mut parts = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        if start > 0 {
+            parts.push(Part::Text(&rest[..start]));
+        }
+        parts.push(Part::Placeholder(rest[start + 2..start + 2 + end].trim()));
+        rest = &rest[start + 2 + end + 2..];
+    }
+    if !rest.is_empty() {
+        parts.push(Part::Text(rest));
+    }
+    parts
+}
+
+impl Snippet {
+    /// 模板中需要用户输入的名称，按出现顺序去重
+    pub fn inputs(&self) -> Vec<String> {
+        let mut names: Vec<String> = Vec::new();
+        for part in parse(&self.template) {
+            if let Part::Placeholder(placeholder) = part {
+                if let Some(name) = placeholder.strip_prefix("input:") {
+                    let name = name.trim().to_string();
+                    if !names.contains(&name) {
+                        names.push(name);
+                    }
+                }
+            }
+        }
+        names
+    }
+
+    /// 渲染模板；缺少输入时返回错误，未知占位符原样保留
+    pub fn render(&self, context: &RenderContext) -> Result<RenderedSnippet> {
+        let missing: Vec<String> = self
+            .inputs()
+            .into_iter()
+            .filter(|name| !context.inputs.contains_key(name))
+            .collect();
+        if !missing.is_empty() {
+            bail!("缺少输入: {}", missing.join(", "));
+        }
+
+        let now = Local::now();
+        let mut text = String::new();
+        let mut cursor = None;
+
+        for part in parse(&self.template) {
+            let placeholder = match part {
+                Part::Text(literal) => {
+                    text.push_str(literal);
+                    continue;
+                }
+                Part::Placeholder(placeholder) => placeholder,
+            };
+
+            let (name, argument) = match placeholder.split_once(':') {
+                Some((name, argument)) => (name.trim(), Some(argument.trim())),
+                None => (placeholder, None),
+            };
+            match (name, argument) {
+                (CURSOR_MARKER, None) => {
+                    cursor.get_or_insert(text.chars().count());
+                }
+                ("date", None) => text.push_str(&now.format("%Y-%m-%d").to_string()),
+                ("time", None) => text.push_str(&now.format("%H:%M:%S").to_string()),
+                ("datetime", None) => text.push_str(&now.format("%Y-%m-%d %H:%M:%S").to_string()),
+                ("date" | "time" | "datetime", Some(format)) => {
+                    use std::fmt::Write;
+                    // 无效的格式字符串会导致 format 返回错误
+                    write!(text, "{}", now.format(format))
+                        .map_err(|_| anyhow::anyhow!("无效的日期格式: {}", format))?;
+                }
+                ("clipboard", None) => text.push_str(context.clipboard.unwrap_or_default()),
+                ("uuid", None) => text.push_str(&uuid::Uuid::new_v4().to_string()),
+                ("input", Some(input)) => text.push_str(&context.inputs[input]),
+                _ => {
+                    text.push_str("{{");
+                    text.push_str(placeholder);
+                    text.push_str("}}");
+                }
+            }
+        }
+
+        Ok(RenderedSnippet { text, cursor })
+    }
+}
+
+/// 片段库，保存在应用数据目录的 snippets.json 中，与剪贴板历史分开存放
+pub struct SnippetStore {
+    path: PathBuf,
+    snippets: Vec<Snippet>,
+}
+
+impl SnippetStore {
+    /// 加载片段库，文件不存在时为空；文件损坏时另存为 .bad 文件并从空片段库开始
+    pub fn load(path: PathBuf) -> Self {
+        let snippets = match std::fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                tracing::warn!("解析片段库失败，已另存为 .bad 文件: {}", e);
+                let _ = std::fs::rename(&path, path.with_extension("bad"));
+                Vec::new()
+            }),
+            Err(_) => Vec::new(),
+        };
+        Self { path, snippets }
+    }
+
+    /// 未删除的片段
+    pub fn list(&self) -> Vec<Snippet> {
+        self.snippets.iter().filter(|snippet| !snippet.deleted).cloned().collect()
+    }
+
+    /// 包括墓碑在内的所有片段，用于同步
+    pub fn all(&self) -> &[Snippet] {
+        &self.snippets
+    }
+
+    pub fn get(&self, id: &str) -> Option<Snippet> {
+        self.snippets.iter().find(|snippet| snippet.id == id && !snippet.deleted).cloned()
+    }
+
+    /// 新增或更新片段
+    pub fn upsert(&mut self, mut snippet: Snippet, now: u64) -> Result<Snippet> {
+        if snippet.id.is_empty() {
+            snippet.id = uuid::Uuid::new_v4().to_string();
+        }
+        snippet.updated_at = now;
+        snippet.deleted = false;
+
+        match self.snippets.iter_mut().find(|existing| existing.id == snippet.id) {
+            Some(existing) => *existing = snippet.clone(),
+            None => self.snippets.push(snippet.clone()),
+        }
+        self.save()?;
+        Ok(snippet)
+    }
+
+    /// 删除片段，保留墓碑
+    pub fn delete(&mut self, id: &str, now: u64) -> Result<()> {
+        let snippet = self.snippets.iter_mut()
+            .find(|snippet| snippet.id == id && !snippet.deleted)
+            .with_context(|| format!("片段不存在: {}", id))?;
+        snippet.deleted = true;
+        snippet.updated_at = now;
+        self.save()
+    }
+
+    /// 合并另一份片段列表，同一 id 以更新时间较新的为准；返回是否有变化
+    pub fn merge(&mut self, others: Vec<Snippet>) -> Result<bool> {
+        let mut changed = false;
+        for other in others {
+            match self.snippets.iter_mut().find(|existing| existing.id == other.id) {
+                Some(existing) if other.updated_at > existing.updated_at => {
+                    *existing = other;
+                    changed = true;
+                }
+                Some(_) => {}
+                None => {
+                    self.snippets.push(other);
+                    changed = true;
+                }
+            }
+        }
+        if changed {
+            self.save()?;
+        }
+        Ok(changed)
+    }
+
+    /// 从文件导入片段，返回新增或更新的数量
+    pub fn import_file(&mut self, path: &Path) -> Result<usize> {
+        let imported = read_snippets_file(path)?;
+        let before: HashMap<String, u64> = self.snippets.iter()
+            .map(|snippet| (snippet.id.clone(), snippet.updated_at))
+            .collect();
+        let count = imported.iter()
+            .filter(|snippet| before.get(&snippet.id).is_none_or(|updated_at| snippet.updated_at > *updated_at))
+            .count();
+        self.merge(imported)?;
+        Ok(count)
+    }
+
+    /// 导出未删除的片段到文件
+    pub fn export_file(&self, path: &Path) -> Result<()> {
+        write_snippets_file(path, &self.list())
+    }
+
+    fn save(&self) -> Result<()> {
+        write_snippets_file(&self.path, &self.snippets)
+    }
+}
+
+fn read_snippets_file(path: &Path) -> Result<Vec<Snippet>> {
+    let content = std::fs::read_to_string(path).context("Failed to read snippets file")?;
+    serde_json::from_str(&content).context("Failed to parse snippets")
+}
+
+fn write_snippets_file(path: &Path, snippets: &[Snippet]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let content = serde_json::to_string_pretty(snippets)?;
+    // 先写临时文件再重命名，避免写入中途崩溃损坏片段库
+    let temp_path = path.with_extension("tmp");
+    std::fs::write(&temp_path, content).context("Failed to save snippets file")?;
+    std::fs::rename(&temp_path, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn corrupt_file_is_set_aside() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("snippets.json");
+        std::fs::write(&path, "{ not json").unwrap();
+
+        let mut store = SnippetStore::load(path.clone());
+        assert!(store.list().is_empty());
+        assert_eq!(std::fs::read_to_string(dir.path().join("snippets.bad")).unwrap(), "{ not json");
+
+        // 之后的保存写入新的片段库
+        let snippet = Snippet {
+            id: String::new(),
+            name: "sig".to_string(),
+            template: "-- {{date}}".to_string(),
+            description: None,
+            updated_at: 0,
+            deleted: false,
+        };
+        let saved = store.upsert(snippet, 1).unwrap();
+        assert_eq!(SnippetStore::load(path).get(&saved.id).map(|snippet| snippet.name), Some("sig".to_string()));
+    }
+}
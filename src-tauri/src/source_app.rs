@@ -0,0 +1,260 @@
+use serde::{Deserialize, Serialize};
+
+/// 前台窗口信息（复制发生时的来源应用）
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct WindowInfo {
+    /// 应用标识，X11 下为 WM_CLASS 的 class 部分，例如 "KeePassXC"
+    pub app_name: Option<String>,
+    /// WM_CLASS 的 instance 部分，例如 "keepassxc"
+    pub app_instance: Option<String>,
+    pub title: Option<String>,
+    pub pid: Option<u32>,
+}
+
+impl WindowInfo {
+    /// 用于展示和存储的来源应用名称
+    pub fn display_name(&self) -> Option<String> {
+        self.app_name.clone().or_else(|| self.app_instance.clone())
+    }
+}
+
+/// 前台窗口信息提供者，抽象出来便于在测试中替换为模拟实现
+pub trait WindowInfoProvider: Send + Sync {
+    fn active_window(&self) -> Option<WindowInfo>;
+}
+
+/// 系统前台窗口信息提供者
+///
+/// Linux 下通过 X11 的 `_NET_ACTIVE_WINDOW` 和 `WM_CLASS` 获取；纯 Wayland 会话没有
+/// 通用的前台窗口查询协议，此时只能借助 XWayland 查询 X11 客户端，查询失败时返回 None。
+pub struct SystemWindowInfoProvider;
+
+impl SystemWindowInfoProvider {
+    pub fn new() -> Self {
+        #[cfg(target_os = "linux")]
+        if std::env::var_os("DISPLAY").is_none() && std::env::var_os("WAYLAND_DISPLAY").is_some() {
+            tracing::warn!("当前为 Wayland 会话且没有 XWayland，无法获取复制来源应用");
+        }
+        Self
+    }
+}
+
+impl Default for SystemWindowInfoProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WindowInfoProvider for SystemWindowInfoProvider {
+    #[cfg(target_os = "linux")]
+    fn active_window(&self) -> Option<WindowInfo> {
+        // Wayland 会话下 DISPLAY 指向 XWayland，只能识别 X11 客户端
+        std::env::var_os("DISPLAY")?;
+        x11::active_window()
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn active_window(&self) -> Option<WindowInfo> {
+        None
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod x11 {
+    use super::WindowInfo;
+    use x11rb::connection::Connection;
+    use x11rb::protocol::xproto::{Atom, AtomEnum, ConnectionExt, Window};
+    use x11rb::rust_connection::RustConnection;
+
+    pub fn active_window() -> Option<WindowInfo> {
+        let (conn, screen_num) = x11rb::connect(None).ok()?;
+        let root = conn.setup().roots.get(screen_num)?.root;
+
+        let active_atom = intern_atom(&conn, b"_NET_ACTIVE_WINDOW")?;
+        let window = conn
+            .get_property(false, root, active_atom, AtomEnum::WINDOW, 0, 1)
+            .ok()?
+            .reply()
+            .ok()?
+            .value32()?
+            .next()?;
+        if window == 0 {
+            return None;
+        }
+
+        // WM_CLASS 格式为 "instance\0class\0"
+        let (app_instance, app_name) = match get_string_property(&conn, window, AtomEnum::WM_CLASS.into(), AtomEnum::STRING.into()) {
+            Some(bytes) => {
+                let mut parts = bytes
+                    .split(|b| *b == 0)
+                    .filter(|part| !part.is_empty())
+                    .map(|part| String::from_utf8_lossy(part).into_owned());
+                (parts.next(), parts.next())
+            }
+            None => (None, None),
+        };
+
+        let title = intern_atom(&conn, b"_NET_WM_NAME")
+            .zip(intern_atom(&conn, b"UTF8_STRING"))
+            .and_then(|(name_atom, utf8_atom)| get_string_property(&conn, window, name_atom, utf8_atom))
+            .map(|bytes| String::from_utf8_lossy(&bytes).into_owned());
+
+        let pid = intern_atom(&conn, b"_NET_WM_PID").and_then(|pid_atom| {
+            conn.get_property(false, window, pid_atom, AtomEnum::CARDINAL, 0, 1)
+                .ok()?
+                .reply()
+                .ok()?
+                .value32()?
+                .next()
+        });
+
+        Some(WindowInfo {
+            app_name,
+            app_instance,
+            title,
+            pid,
+        })
+    }
+
+    fn intern_atom(conn: &RustConnection, name: &[u8]) -> Option<Atom> {
+        Some(conn.intern_atom(false, name).ok()?.reply().ok()?.atom)
+    }
+
+    fn get_string_property(conn: &RustConnection, window: Window, property: Atom, type_: Atom) -> Option<Vec<u8>> {
+        let reply = conn
+            .get_property(false, window, property, type_, 0, 1024)
+            .ok()?
+            .reply()
+            .ok()?;
+        if reply.value.is_empty() {
+            None
+        } else {
+            Some(reply.value)
+        }
+    }
+}
+
+/// 过滤模式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AppFilterMode {
+    /// 黑名单：列表中的应用不记录
+    #[default]
+    Deny,
+    /// 白名单：只记录列表中的应用
+    Allow,
+}
+
+/// 按来源应用过滤剪贴板记录的规则
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppFilter {
+    pub mode: AppFilterMode,
+    /// 应用名称列表，不区分大小写地匹配 WM_CLASS 的 class 或 instance
+    pub apps: Vec<String>,
+    /// 无法获取来源应用时是否记录
+    pub capture_unknown: bool,
+}
+
+impl Default for AppFilter {
+    fn default() -> Self {
+        Self {
+            mode: AppFilterMode::Deny,
+            apps: vec![
+                "keepassxc".to_string(),
+                "1password".to_string(),
+                "bitwarden".to_string(),
+                "enpass".to_string(),
+            ],
+            capture_unknown: true,
+        }
+    }
+}
+
+impl AppFilter {
+    /// 判断给定应用是否在列表中
+    fn matches(&self, window: &WindowInfo) -> bool {
+        let candidates = [window.app_name.as_deref(), window.app_instance.as_deref()];
+        self.apps.iter().any(|app| {
+            candidates
+                .iter()
+                .flatten()
+                .any(|candidate| candidate.eq_ignore_ascii_case(app.trim()))
+        })
+    }
+
+    /// 判断来自该窗口的复制是否应该被记录
+    pub fn should_capture(&self, window: Option<&WindowInfo>) -> bool {
+        let window = match window {
+            Some(window) if window.app_name.is_some() || window.app_instance.is_some() => window,
+            _ => return self.capture_unknown,
+        };
+
+        match self.mode {
+            AppFilterMode::Deny => !self.matches(window),
+            AppFilterMode::Allow => self.matches(window),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 固定返回同一个前台窗口的模拟提供者
+    struct MockWindowInfoProvider(Option<WindowInfo>);
+
+    impl WindowInfoProvider for MockWindowInfoProvider {
+        fn active_window(&self) -> Option<WindowInfo> {
+            self.0.clone()
+        }
+    }
+
+    fn window(app_name: &str, app_instance: &str) -> MockWindowInfoProvider {
+        MockWindowInfoProvider(Some(WindowInfo {
+            app_name: Some(app_name.to_string()),
+            app_instance: Some(app_instance.to_string()),
+            title: Some("Untitled".to_string()),
+            pid: Some(42),
+        }))
+    }
+
+    fn should_capture(filter: &AppFilter, provider: &dyn WindowInfoProvider) -> bool {
+        filter.should_capture(provider.active_window().as_ref())
+    }
+
+    #[test]
+    fn deny_list_skips_listed_apps() {
+        let filter = AppFilter::default();
+        assert!(!should_capture(&filter, &window("KeePassXC", "keepassxc")));
+        assert!(!should_capture(&filter, &window("Bitwarden", "bitwarden")));
+        assert!(should_capture(&filter, &window("Firefox", "Navigator")));
+    }
+
+    #[test]
+    fn allow_list_only_captures_listed_apps() {
+        let filter = AppFilter {
+            mode: AppFilterMode::Allow,
+            apps: vec![" Code ".to_string(), "gnome-terminal-server".to_string()],
+            capture_unknown: false,
+        };
+        assert!(should_capture(&filter, &window("code", "code")));
+        assert!(should_capture(&filter, &window("Gnome-terminal", "gnome-terminal-server")));
+        assert!(!should_capture(&filter, &window("KeePassXC", "keepassxc")));
+    }
+
+    #[test]
+    fn unknown_window_follows_capture_unknown() {
+        let mut filter = AppFilter::default();
+        let unknown = MockWindowInfoProvider(None);
+        let untitled = MockWindowInfoProvider(Some(WindowInfo {
+            title: Some("No class".to_string()),
+            ..Default::default()
+        }));
+        assert!(should_capture(&filter, &unknown));
+        assert!(should_capture(&filter, &untitled));
+
+        filter.capture_unknown = false;
+        assert!(!should_capture(&filter, &unknown));
+        assert!(!should_capture(&filter, &untitled));
+    }
+}
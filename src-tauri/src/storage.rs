@@ -1,27 +1,82 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs::{File, OpenOptions};
 use std::io::{BufReader, BufWriter, Read, Write};
 use std::path::PathBuf;
 use serde::Serialize;
 
+use crate::text_classify::DetectedKind;
+use crate::blob_store::BlobStore;
+use crate::sync_outbox::{referenced_blobs, OUTBOX_FILE_NAME};
+
 // 剪贴板历史数据结构
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
 pub struct ClipboardItem {
     pub id: String,
     pub content: String,
     pub timestamp: u64,
-    pub item_type: String, // "text" 或 "files"
+    pub item_type: String, // "text"、"files" 或 "image"
     pub size: Option<u64>,
     pub file_paths: Option<Vec<String>>,
     pub file_types: Option<Vec<FileTypeInfo>>, // 文件类型信息
+    #[serde(default)]
+    pub source_app: Option<String>, // 复制来源应用
+    #[serde(default)]
+    pub image: Option<ImageInfo>, // 图片信息，图片数据本身保存在 blob 存储中
+    #[serde(default)]
+    pub html: Option<String>, // 与纯文本一起复制的 HTML 格式
+    #[serde(default)]
+    pub rtf: Option<String>, // 与纯文本一起复制的 RTF 格式
+    #[serde(default)]
+    pub use_count: u32, // 从历史中再次复制的次数
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detected_kind: Option<DetectedKind>, // 文本内容的识别结果
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>, // 规则添加的标签
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pinned: bool, // 置顶项目排在历史最前面
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>, // 到期时间（Unix 秒），到期后自动删除
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub exclude_from_sync: bool, // 只保存在本机，不上传到同步存储
+}
+
+impl ClipboardItem {
+    // 项目引用的所有 blob：图片数据和文件副本
+    pub fn blob_hashes(&self) -> Vec<String> {
+        let mut hashes: Vec<String> = self.image.iter().map(|image| image.hash.clone()).collect();
+        if let Some(file_types) = &self.file_types {
+            hashes.extend(file_types.iter().filter_map(|info| info.snapshot.clone()));
+        }
+        hashes
+    }
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ImageInfo {
+    pub hash: String, // PNG 数据的内容哈希，即 blob 地址
+    pub width: u32,
+    pub height: u32,
+    pub thumbnail: String, // base64 编码的 PNG 缩略图
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
 pub struct FileTypeInfo {
     pub path: String,
     pub file_type: String, // 检测的文件类型
     pub mime_type: String, // MIME 类型
     pub category: String, // 文件类别，如 "image", "document", "code" 等
+    #[serde(default)]
+    pub size: u64, // 文件大小，目录为递归统计的总大小
+    #[serde(default)]
+    pub is_dir: bool,
+    #[serde(default)]
+    pub is_symlink: bool,
+    #[serde(default)]
+    pub entry_count: Option<u64>, // 目录内的条目数
+    #[serde(default)]
+    pub truncated: bool, // 目录条目过多，统计不完整
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snapshot: Option<String>, // 保留副本时文件内容的 blob 地址
 }
 
 // 操作类型
@@ -57,6 +112,7 @@ pub struct StorageEngine {
     file: BufWriter<File>,
     index: HashMap<String, ClipboardItem>, // 内存索引，key为item_id
     deleted_items: HashMap<String, u64>,   // 已删除项目，key为item_id，value为删除时间戳
+    blobs: BlobStore,                      // 图片等二进制数据
 }
 
 impl StorageEngine {
@@ -65,6 +121,7 @@ impl StorageEngine {
         std::fs::create_dir_all(&storage_dir)?;
         
         let file_path = storage_dir.join("clipboard.log");
+        let blobs = BlobStore::new(storage_dir.join("blobs"))?;
         
         // 打开或创建文件
         let file = OpenOptions::new()
@@ -77,6 +134,7 @@ impl StorageEngine {
             file: BufWriter::new(file),
             index: HashMap::new(),
             deleted_items: HashMap::new(),
+            blobs,
         };
         
         // 恢复数据
@@ -119,7 +177,7 @@ impl StorageEngine {
             }
         }
         
-        println!("恢复了 {} 个剪切板项目", self.index.len());
+        tracing::info!("恢复了 {} 个剪切板项目", self.index.len());
         Ok(())
     }
     
@@ -246,20 +304,69 @@ impl StorageEngine {
     // 获取所有有效的剪切板项目
     pub fn get_all(&self) -> Vec<ClipboardItem> {
         let mut items: Vec<ClipboardItem> = self.index.values().cloned().collect();
-        // 按时间戳倒序排列（最新的在前面）
-        items.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+        // 置顶项目在前，其余按时间戳倒序排列（最新的在前面）
+        items.sort_by(|a, b| b.pinned.cmp(&a.pinned).then(b.timestamp.cmp(&a.timestamp)));
         items
     }
     
-    // 清空所有数据（标记所有项目为删除）
-    pub fn clear_all(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        let item_ids: Vec<String> = self.index.keys().cloned().collect();
+    // 获取单个项目
+    pub fn get(&self, item_id: &str) -> Option<ClipboardItem> {
+        self.index.get(item_id).cloned()
+    }
+    
+    // 查找满足条件的项目
+    pub fn find<F: Fn(&ClipboardItem) -> bool>(&self, predicate: F) -> Option<ClipboardItem> {
+        self.index.values().find(|item| predicate(item)).cloned()
+    }
+    
+    // 按图片内容哈希查找已有项目，用于去重
+    pub fn find_by_content_hash(&self, hash: &str) -> Option<ClipboardItem> {
+        self.find(|item| item.image.as_ref().is_some_and(|image| image.hash == hash))
+    }
+    
+    // 记录一次使用：更新时间戳并增加使用次数，返回更新后的项目
+    pub fn touch(&mut self, item_id: &str) -> Result<Option<ClipboardItem>, Box<dyn std::error::Error>> {
+        let mut item = match self.index.get(item_id) {
+            Some(item) => item.clone(),
+            None => return Ok(None),
+        };
         
-        for item_id in item_ids {
-            self.delete(&item_id)?;
+        item.timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_secs();
+        item.use_count += 1;
+        self.insert(&item)?;
+        
+        Ok(Some(item))
+    }
+    
+    // 删除到期的项目，返回被删除的项目
+    pub fn remove_expired(&mut self, now: u64) -> Result<Vec<ClipboardItem>, Box<dyn std::error::Error>> {
+        let expired: Vec<ClipboardItem> = self.index
+            .values()
+            .filter(|item| item.expires_at.is_some_and(|expires_at| expires_at <= now))
+            .cloned()
+            .collect();
+        for item in &expired {
+            self.delete(&item.id)?;
         }
+        Ok(expired)
+    }
+    
+    // 二进制数据存储
+    pub fn blobs(&self) -> &BlobStore {
+        &self.blobs
+    }
+    
+    // 清空所有数据（标记所有项目为删除），返回被删除的项目
+    pub fn clear_all(&mut self) -> Result<Vec<ClipboardItem>, Box<dyn std::error::Error>> {
+        let items: Vec<ClipboardItem> = self.index.values().cloned().collect();
         
-        Ok(())
+        for item in &items {
+            self.delete(&item.id)?;
+        }
+        
+        Ok(items)
     }
     
     // 获取存储统计信息
@@ -270,6 +377,7 @@ impl StorageEngine {
             file_size: std::fs::metadata(&self.file_path)
                 .map(|m| m.len())
                 .unwrap_or(0),
+            blob_size: self.blobs.total_size(),
         }
     }
     
@@ -332,7 +440,24 @@ impl StorageEngine {
         // 清空删除列表
         self.deleted_items.clear();
         
-        println!("存储文件压缩完成");
+        // 清理不再被引用的 blob；同步发件箱中等待上传的操作引用的 blob 也要保留，
+        // 无法读取发件箱时跳过清理
+        let mut referenced: HashSet<String> = self.index.values()
+            .flat_map(|item| item.blob_hashes())
+            .collect();
+        let outbox_path = self.file_path.with_file_name(OUTBOX_FILE_NAME);
+        match referenced_blobs(&outbox_path) {
+            Ok(pending) => {
+                referenced.extend(pending);
+                let removed = self.blobs.retain(&referenced)?;
+                if removed > 0 {
+                    tracing::info!("清理了 {} 个未引用的 blob", removed);
+                }
+            }
+            Err(e) => tracing::warn!("读取同步发件箱失败，跳过清理 blob: {:#}", e),
+        }
+        
+        tracing::info!("存储文件压缩完成");
         Ok(())
     }
     
@@ -375,4 +500,5 @@ pub struct StorageStats {
     pub total_items: usize,
     pub deleted_items: usize,
     pub file_size: u64,
+    pub blob_size: u64,
 } 
\ No newline at end of file
@@ -1,12 +1,20 @@
-use anyhow::{Context, Result};
+use anyhow::{anyhow, ensure, Context, Result};
 use chrono::{DateTime, Utc};
 use opendal::Operator;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use tokio::sync::{Mutex, RwLock};
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::{watch, Mutex, RwLock};
 use uuid::Uuid;
 
-use crate::storage::ClipboardItem;
+use crate::blob_store::BlobStore;
+use crate::storage::{ClipboardItem, FileTypeInfo, ImageInfo};
+use crate::sync_clock::{Hlc, HybridClock};
+use crate::sync_outbox::{Outbox, OutboxEntry};
+use crate::snippets::Snippet;
+use crate::text_classify::classify;
 
 /// LWW-Oplog 中的剪切板条目
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -23,6 +31,16 @@ pub struct ItemMetadata {
     pub source_device: String,
     pub source_app: Option<String>,
     pub content_hash: Option<String>, // 用于大文件的内容引用
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<ImageInfo>, // 图片尺寸和缩略图，图片数据通过 content_hash 引用 data/ 下的对象
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub html: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rtf: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_paths: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_types: Option<Vec<FileTypeInfo>>, // 保留副本的文件通过 snapshot 引用 data/ 下的对象
 }
 
 /// 操作类型
@@ -32,6 +50,9 @@ pub enum OpType {
     Add,
     #[serde(rename = "DELETE")]
     Delete,
+    /// 一次删除多个项目，如清空历史
+    #[serde(rename = "DELETE_BATCH")]
+    DeleteBatch,
 }
 
 /// LWW-Oplog 中的操作记录
@@ -40,50 +61,98 @@ pub struct Operation {
     pub op_id: String,
     pub op_type: OpType,
     pub target_id: String,
-    pub timestamp: DateTime<Utc>,
+    pub timestamp: DateTime<Utc>, // 墙上时间，只用于显示；排序和冲突解决使用 hlc
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hlc: Option<Hlc>, // 产生操作的设备的混合逻辑时钟，旧版本的操作没有
     pub device_id: String, // 用于打破时间戳平局
     pub payload: Option<SyncClipboardItem>, // ADD操作时包含完整数据，DELETE时为None
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub target_ids: Vec<String>, // DELETE_BATCH 操作删除的项目，此时 target_id 为空
 }
 
 impl Operation {
     /// 创建新的 ADD 操作
-    pub fn new_add(item: SyncClipboardItem, device_id: String) -> Self {
+    pub fn new_add(item: SyncClipboardItem, device_id: String, hlc: Hlc) -> Self {
         Self {
             op_id: Uuid::new_v4().to_string(),
             op_type: OpType::Add,
             target_id: item.id.clone(),
             timestamp: Utc::now(),
+            hlc: Some(hlc),
             device_id,
             payload: Some(item),
+            target_ids: Vec::new(),
         }
     }
 
     /// 创建新的 DELETE 操作
-    pub fn new_delete(target_id: String, device_id: String) -> Self {
+    pub fn new_delete(target_id: String, device_id: String, hlc: Hlc) -> Self {
         Self {
             op_id: Uuid::new_v4().to_string(),
             op_type: OpType::Delete,
             target_id,
             timestamp: Utc::now(),
+            hlc: Some(hlc),
             device_id,
             payload: None,
+            target_ids: Vec::new(),
+        }
+    }
+
+    /// 创建新的 DELETE_BATCH 操作
+    pub fn new_delete_batch(target_ids: Vec<String>, device_id: String, hlc: Hlc) -> Self {
+        Self {
+            op_id: Uuid::new_v4().to_string(),
+            op_type: OpType::DeleteBatch,
+            target_id: String::new(),
+            timestamp: Utc::now(),
+            hlc: Some(hlc),
+            device_id,
+            payload: None,
+            target_ids,
         }
     }
 
     /// 比较两个操作的时间戳，实现 LWW 逻辑
     /// 返回 true 表示 self 比 other 更新（应该获胜）
     pub fn is_newer_than(&self, other: &Operation) -> bool {
-        match self.timestamp.cmp(&other.timestamp) {
-            std::cmp::Ordering::Greater => true,
-            std::cmp::Ordering::Less => false,
-            std::cmp::Ordering::Equal => {
-                // 时间戳相同时，比较设备ID的字典序
-                self.device_id > other.device_id
-            }
+        self.version() > other.version()
+    }
+
+    /// 操作的混合逻辑时钟，旧版本的操作按墙上时间计算
+    pub fn clock(&self) -> Hlc {
+        self.hlc.unwrap_or_else(|| Hlc::from(self.timestamp))
+    }
+
+    /// 操作在 LWW 比较中的版本
+    pub fn version(&self) -> OpVersion {
+        OpVersion {
+            hlc: self.clock(),
+            device_id: self.device_id.clone(),
+            op_id: self.op_id.clone(),
+        }
+    }
+
+    /// 操作影响的项目
+    pub fn targets(&self) -> Vec<&str> {
+        match self.op_type {
+            OpType::DeleteBatch => self.target_ids.iter().map(String::as_str).collect(),
+            _ => vec![self.target_id.as_str()],
         }
     }
 }
 
+/// 操作的版本，按混合逻辑时钟、设备ID、操作ID依次比较
+///
+/// 时钟相同时比较设备ID的字典序，同一设备再比较操作ID，
+/// 任意两个不同的操作都能分出先后，所有设备得到相同的结果。
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct OpVersion {
+    pub hlc: Hlc,
+    pub device_id: String,
+    pub op_id: String,
+}
+
 /// 状态快照
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Snapshot {
@@ -91,8 +160,53 @@ pub struct Snapshot {
     pub snapshot_timestamp: DateTime<Utc>,
     pub last_op_timestamp: DateTime<Utc>,
     pub device_id: String,
+    /// 每个项目获胜操作的版本，包括已删除项目的墓碑
+    #[serde(default)]
+    pub versions: HashMap<String, OpVersion>,
+    /// 快照包含的每个设备的最后一个操作
+    #[serde(default)]
+    pub cursors: HashMap<String, Hlc>,
+}
+
+/// snapshots/latest.json 的内容，指向最新快照
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotInfo {
+    pub snapshot_path: String,
+    pub timestamp: DateTime<Utc>,
+    /// 快照包含的每个设备的最后一个操作，清理操作日志时以此为界
+    #[serde(default)]
+    pub cursors: HashMap<String, Hlc>,
+    /// 每个设备已从 oplog 中清理的最后一个操作；本地游标落后于它时需要重新加载快照
+    #[serde(default)]
+    pub pruned: HashMap<String, Hlc>,
 }
 
+/// 快照租约，同一时间只有一个设备生成快照和清理操作日志
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SnapshotLease {
+    device_id: String,
+    expires_at: DateTime<Utc>,
+    /// 每次获取租约时随机生成，用于确认租约没有被其他设备改写
+    #[serde(default)]
+    token: String,
+}
+
+// 后台维护（检查快照、清理操作日志）的间隔
+const MAINTENANCE_INTERVAL: Duration = Duration::from_secs(600);
+// 快照租约的有效期，持有租约的设备应在此期间内完成快照和清理
+const LEASE_SECONDS: i64 = 300;
+// 写入租约后等待一段时间再读取确认，其他设备同时写入时以最后写入的为准
+const LEASE_SETTLE_DELAY: Duration = Duration::from_secs(2);
+// 租约剩余时间少于该值时不再继续修改快照和操作日志，留出余量应对设备间的时钟误差
+const LEASE_MARGIN_SECONDS: i64 = 60;
+// 清理操作日志时每批删除的对象数，每批之前确认租约仍然有效
+const PRUNE_BATCH_SIZE: usize = 100;
+
+// 上传失败后第一次重试前的等待时间，之后每次翻倍
+const INITIAL_RETRY_DELAY: Duration = Duration::from_millis(500);
+// 重试等待时间的上限
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
 /// 同步配置
 #[derive(Debug, Clone)]
 pub struct SyncConfig {
@@ -100,14 +214,179 @@ pub struct SyncConfig {
     pub device_id: String,
     pub storage_operator: Operator,
     pub sync_interval_seconds: u64,
+    /// 请求对象存储失败后的重试次数
+    pub retry_attempts: usize,
+    /// 单次请求的超时时间
+    pub timeout_seconds: u64,
+    /// 本地发件箱文件，保存尚未确认上传的操作
+    pub outbox_path: PathBuf,
+    /// 本设备混合逻辑时钟的状态文件
+    pub clock_path: PathBuf,
+    /// 本地保存的同步状态文件，包括每个设备的游标
+    pub state_path: PathBuf,
+    /// 一个操作段最多包含的操作数，发件箱积压达到该数量时立即上传
+    pub segment_max_ops: usize,
+    /// 本地操作等待合并的时间，窗口内的操作写入同一个段
+    pub segment_window_seconds: u64,
+    /// 距上次快照累计多少个操作后生成新快照
+    pub snapshot_every_ops: usize,
+    /// 距上次快照超过该时间且有新操作时生成新快照
+    pub snapshot_interval_seconds: u64,
+    /// 保留最近的快照数量
+    pub snapshot_keep: usize,
+    /// 操作被快照包含后至少再保留的时间，离线较久的设备回来后仍能拉取
+    pub oplog_retention_seconds: u64,
+    /// 本地 blob 存储，上传操作前从中读取引用的图片和文件副本
+    pub blobs: BlobStore,
+}
+
+/// 远端操作对本地状态造成的变化
+#[derive(Debug, Clone, Default)]
+pub struct SyncDelta {
+    /// 新增或被更新的项目
+    pub added: Vec<SyncClipboardItem>,
+    /// 被删除的项目 id
+    pub removed: Vec<String>,
 }
 
+impl SyncDelta {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+
+    fn add(&mut self, item: SyncClipboardItem) {
+        self.removed.retain(|id| *id != item.id);
+        self.added.retain(|added| added.id != item.id);
+        self.added.push(item);
+    }
+
+    fn remove(&mut self, item_id: &str) {
+        self.added.retain(|added| added.id != item_id);
+        if !self.removed.iter().any(|id| id == item_id) {
+            self.removed.push(item_id.to_string());
+        }
+    }
+}
+
+// 远端变化的处理函数，由应用把变化写入本地历史
+type RemoteChangeHandler = Box<dyn Fn(SyncDelta) + Send + Sync>;
+
 /// 同步状态
-#[derive(Debug, Default)]
+///
+/// 按 LWW-element-set 维护：每个项目只保留版本最新的操作，
+/// 删除后版本作为墓碑保留，较早的 ADD 晚到时不会让项目复活。
+/// 操作以任意顺序、任意次数应用，结果都相同。
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct SyncState {
+    pub remote: String, // 状态对应的对象存储和用户，同步配置改变后作废
     pub items: HashMap<String, SyncClipboardItem>, // 当前状态，key为item_id
-    pub last_sync_timestamp: Option<DateTime<Utc>>, // 上次同步的时间戳
-    pub pending_ops: Vec<Operation>, // 待上传的操作队列
+    pub versions: HashMap<String, OpVersion>, // 每个项目获胜操作的版本，包括墓碑
+    pub cursors: HashMap<String, Hlc>, // 每个设备已应用的最后一个操作的时钟
+    pub legacy_applied: HashSet<String>, // 已应用的旧版本操作（直接保存在 oplog/ 下）的 op_id
+    pub last_sync_timestamp: Option<DateTime<Utc>>, // 上次同步完成的时间
+    pub snapshot_timestamp: Option<DateTime<Utc>>, // 已知的最新快照的生成时间
+    pub ops_since_snapshot: usize, // 最新快照之后应用的操作数
+}
+
+impl SyncState {
+    /// 加载本地保存的同步状态；文件不存在、损坏或属于其他存储时从空状态开始
+    pub fn load(path: &Path, remote: &str) -> Self {
+        let state = match std::fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str::<Self>(&content)
+                .inspect_err(|e| tracing::warn!("解析同步状态失败，将重新同步: {}", e))
+                .ok(),
+            Err(_) => None,
+        };
+        match state {
+            Some(state) if state.remote == remote => state,
+            _ => Self {
+                remote: remote.to_string(),
+                ..Default::default()
+            },
+        }
+    }
+
+    /// 合并快照：快照中项目的版本比本地新时采用快照的结果，游标取两者中较大的
+    ///
+    /// 和逐个应用操作一样满足交换律，快照和操作以任意顺序合并结果都相同。
+    pub fn merge_snapshot(&mut self, snapshot: Snapshot, delta: &mut SyncDelta) {
+        let mut items: HashMap<String, SyncClipboardItem> = snapshot
+            .items
+            .into_iter()
+            .map(|item| (item.id.clone(), item))
+            .collect();
+
+        for (target_id, version) in snapshot.versions {
+            let item = items.remove(&target_id);
+            if self.versions.get(&target_id).is_some_and(|current| *current >= version) {
+                continue;
+            }
+            match item {
+                Some(item) => {
+                    self.items.insert(target_id.clone(), item.clone());
+                    delta.add(item);
+                }
+                None => {
+                    self.items.remove(&target_id);
+                    delta.remove(&target_id);
+                }
+            }
+            self.versions.insert(target_id, version);
+        }
+
+        // 旧版本的快照没有版本信息，只补充本地没有的项目
+        for (target_id, item) in items {
+            if !self.versions.contains_key(&target_id) && !self.items.contains_key(&target_id) {
+                self.items.insert(target_id, item.clone());
+                delta.add(item);
+            }
+        }
+
+        for (device_id, cursor) in snapshot.cursors {
+            let current = self.cursors.entry(device_id).or_default();
+            *current = (*current).max(cursor);
+        }
+    }
+
+    fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let content = serde_json::to_string(self)?;
+        let temp_path = path.with_extension("tmp");
+        std::fs::write(&temp_path, content).context("Failed to save sync state")?;
+        std::fs::rename(&temp_path, path)?;
+        Ok(())
+    }
+
+    /// 应用一个操作，状态的变化记录到 delta 中；操作不比项目当前的版本新时忽略
+    pub fn apply(&mut self, op: &Operation, delta: &mut SyncDelta) {
+        // 没有数据的 ADD 操作无法应用
+        if op.op_type == OpType::Add && op.payload.is_none() {
+            return;
+        }
+
+        let version = op.version();
+        for target_id in op.targets() {
+            if self.versions.get(target_id).is_some_and(|current| *current >= version) {
+                continue;
+            }
+            self.versions.insert(target_id.to_string(), version.clone());
+
+            match &op.payload {
+                Some(item) if op.op_type == OpType::Add => {
+                    self.items.insert(target_id.to_string(), item.clone());
+                    delta.add(item.clone());
+                }
+                // 本机状态中没有该项目时，本地历史中仍可能有，一并删除
+                _ => {
+                    self.items.remove(target_id);
+                    delta.remove(target_id);
+                }
+            }
+        }
+    }
 }
 
 /// 核心同步引擎
@@ -115,49 +394,127 @@ pub struct SyncEngine {
     config: SyncConfig,
     state: RwLock<SyncState>,
     is_syncing: Mutex<bool>,
+    outbox: Mutex<Outbox>,
+    clock: std::sync::Mutex<HybridClock>,
+    // 同一时间只有一个任务上传发件箱，避免重复上传
+    flushing: Mutex<()>,
+    remote_handler: std::sync::Mutex<Option<RemoteChangeHandler>>,
+    // 引擎被替换时设为 true，后台同步随之退出，之后不再修改发件箱、时钟和同步状态文件
+    stopped: watch::Sender<bool>,
 }
 
 impl SyncEngine {
     pub fn new(config: SyncConfig) -> Self {
+        let outbox = Outbox::load(config.outbox_path.clone());
+        let mut clock = HybridClock::load(config.clock_path.clone());
+        // 时钟文件丢失时，新操作的时钟仍然要排在发件箱中的操作之后
+        if let Some(last_clock) = outbox.last_clock() {
+            if let Err(e) = clock.observe(last_clock) {
+                tracing::warn!("保存同步时钟失败: {:#}", e);
+            }
+        }
+        // 重启后从保存的游标继续同步，不需要重新加载快照和全部操作
+        let info = config.storage_operator.info();
+        let remote = format!("{}://{}{}#{}", info.scheme(), info.name(), info.root(), config.user_id);
+        let state = SyncState::load(&config.state_path, &remote);
         Self {
             config,
-            state: RwLock::new(SyncState::default()),
+            state: RwLock::new(state),
             is_syncing: Mutex::new(false),
+            outbox: Mutex::new(outbox),
+            clock: std::sync::Mutex::new(clock),
+            flushing: Mutex::new(()),
+            remote_handler: std::sync::Mutex::new(None),
+            stopped: watch::Sender::new(false),
         }
     }
 
-    /// 本地添加操作（当用户复制新内容时）
-    pub async fn local_add(&self, item: SyncClipboardItem) -> Result<()> {
-        let op = Operation::new_add(item.clone(), self.config.device_id.clone());
-        
-        // 立即更新本地状态
+    /// 停止引擎：结束后台同步，等待进行中的同步完成，上传发件箱中剩余的操作并保存同步状态
+    ///
+    /// 发件箱、时钟和同步状态保存在固定的文件中，重新加载引擎前必须先停止旧引擎，
+    /// 否则两个引擎各自持有一份内存副本，保存时互相覆盖，未上传的操作会丢失。
+    pub async fn shutdown(&self) {
+        self.stopped.send_replace(true);
+
+        // 进行中的同步和维护结束后才会释放 is_syncing，之后的同步直接返回
+        let _lock = self.is_syncing.lock().await;
         {
-            let mut state = self.state.write().await;
-            state.items.insert(item.id.clone(), item);
-            state.pending_ops.push(op);
+            // 已经写入发件箱的本地操作在锁内应用到同步状态，之后的本地操作都会被拒绝
+            let _outbox = self.outbox.lock().await;
         }
 
-        // 异步上传操作
-        self.upload_pending_ops().await?;
-        
+        let _flushing = self.flushing.lock().await;
+        if let Err(e) = self.upload_outbox().await {
+            tracing::warn!("停止同步引擎时上传发件箱失败，操作保留到下次上传: {:#}", e);
+        }
+        if let Err(e) = self.state.read().await.save(&self.config.state_path) {
+            tracing::warn!("保存同步状态失败: {:#}", e);
+        }
+        tracing::info!("同步引擎已停止");
+    }
+
+    fn is_stopped(&self) -> bool {
+        *self.stopped.borrow()
+    }
+
+    fn ensure_running(&self) -> Result<()> {
+        if self.is_stopped() {
+            anyhow::bail!("同步引擎已停止");
+        }
         Ok(())
     }
 
+    /// 设置远端变化的处理函数；应用远端操作并下载引用的 blob 后调用
+    ///
+    /// 只包含其他设备产生的变化，本机的操作不会再传回本机。
+    pub fn on_remote_changes<F: Fn(SyncDelta) + Send + Sync + 'static>(&self, handler: F) {
+        *self.remote_handler.lock().unwrap() = Some(Box::new(handler));
+    }
+
+    /// 本地添加操作（当用户复制新内容时），blobs 为项目引用的图片数据和文件副本
+    pub async fn local_add(&self, item: SyncClipboardItem, blobs: Vec<String>) -> Result<()> {
+        self.push_local(|device_id, hlc| Operation::new_add(item, device_id, hlc), blobs).await
+    }
+
     /// 本地删除操作（当用户删除历史记录时）
     pub async fn local_delete(&self, item_id: String) -> Result<()> {
-        let op = Operation::new_delete(item_id.clone(), self.config.device_id.clone());
-        
-        // 立即更新本地状态
-        {
-            let mut state = self.state.write().await;
-            state.items.remove(&item_id);
-            state.pending_ops.push(op);
+        self.push_local(|device_id, hlc| Operation::new_delete(item_id, device_id, hlc), Vec::new()).await
+    }
+
+    /// 本地批量删除操作（清空历史、删除到期项目），所有项目只生成一个操作
+    pub async fn local_delete_batch(&self, item_ids: Vec<String>) -> Result<()> {
+        if item_ids.is_empty() {
+            return Ok(());
         }
+        self.push_local(|device_id, hlc| Operation::new_delete_batch(item_ids, device_id, hlc), Vec::new()).await
+    }
 
-        // 异步上传操作
-        self.upload_pending_ops().await?;
-        
-        Ok(())
+    // 记录本地操作后等待一个合并窗口再上传，窗口内的其他操作写入同一个段
+    async fn push_local<F>(&self, new_op: F, blobs: Vec<String>) -> Result<()>
+    where
+        F: FnOnce(String, Hlc) -> Operation,
+    {
+        // 先写入发件箱，上传失败或应用退出后下次继续上传；
+        // 在发件箱锁内生成时钟，发件箱中操作的顺序和时钟顺序一致；
+        // 同样在锁内应用到同步状态，停止引擎时不会漏掉已经写入发件箱的操作
+        let depth = {
+            let mut outbox = self.outbox.lock().await;
+            self.ensure_running()?;
+            let op = new_op(self.config.device_id.clone(), self.tick()?);
+            outbox.push(op.clone(), blobs)?;
+            self.state.write().await.apply(&op, &mut SyncDelta::default());
+            outbox.len()
+        };
+
+        if depth < self.config.segment_max_ops {
+            tokio::time::sleep(Duration::from_secs(self.config.segment_window_seconds)).await;
+        }
+        self.flush_outbox().await
+    }
+
+    // 为本地操作生成时钟
+    fn tick(&self) -> Result<Hlc> {
+        self.clock.lock().unwrap().tick()
     }
 
     /// 获取当前所有剪切板项目
@@ -166,26 +523,172 @@ impl SyncEngine {
         state.items.values().cloned().collect()
     }
 
-    /// 上传待处理的操作到对象存储
-    async fn upload_pending_ops(&self) -> Result<()> {
-        let ops_to_upload = {
-            let mut state = self.state.write().await;
-            let ops = state.pending_ops.clone();
-            state.pending_ops.clear();
-            ops
-        };
+    /// 按加入顺序分段上传发件箱中的操作，遇到失败时停止，剩余操作留到下次上传
+    pub async fn flush_outbox(&self) -> Result<()> {
+        let _flushing = self.flushing.lock().await;
+        // 引擎已停止时发件箱文件可能已由新的引擎接管
+        if self.is_stopped() {
+            return Ok(());
+        }
+        self.upload_outbox().await
+    }
 
-        for op in ops_to_upload {
-            let path = format!("{}/oplog/{}.json", self.config.user_id, op.op_id);
-            let content = serde_json::to_vec(&op)
-                .context("Failed to serialize operation")?;
-            
-            self.config.storage_operator
-                .write(&path, content)
-                .await
-                .context("Failed to upload operation")?;
+    // 调用方需要持有 flushing 锁
+    async fn upload_outbox(&self) -> Result<()> {
+        loop {
+            let entries = self.outbox.lock().await.peek(self.config.segment_max_ops.max(1));
+            if entries.is_empty() {
+                return Ok(());
+            }
+            let op_ids: Vec<String> = entries.iter().map(|entry| entry.op.op_id.clone()).collect();
+
+            match self.upload_segment(&entries).await {
+                Ok(()) => self.outbox.lock().await.remove(&op_ids)?,
+                Err(e) => {
+                    self.outbox.lock().await.record_failure(&op_ids, &format!("{:#}", e))?;
+                    return Err(e);
+                }
+            }
+        }
+    }
+
+    // 先上传操作引用的 blob，保证其他设备拿到操作时数据已经可用；
+    // 然后把这些操作写成一个段对象，每行一个操作（JSON Lines）
+    async fn upload_segment(&self, entries: &[OutboxEntry]) -> Result<()> {
+        for hash in entries.iter().flat_map(|entry| &entry.blobs) {
+            let data = self.config.blobs.get(hash).map_err(|e| e.to_string());
+            match data {
+                Ok(data) => self.upload_blob(hash, data).await?,
+                // 项目已在本地删除并压缩，数据无法再上传
+                Err(e) => tracing::warn!("读取 blob {} 失败，跳过上传: {}", hash, e),
+            }
+        }
+
+        let ops: Vec<&Operation> = entries.iter().map(|entry| &entry.op).collect();
+        let path = format!("{}/oplog/{}/{}", self.config.user_id, self.config.device_id, segment_name(&ops));
+        let mut content = Vec::new();
+        for op in ops {
+            serde_json::to_writer(&mut content, op).context("Failed to serialize operation")?;
+            content.push(b'\n');
+        }
+        self.write_with_retry(&path, content)
+            .await
+            .context("Failed to upload operation segment")
+    }
+
+    /// 上传二进制数据到 data/ 目录，已存在时跳过
+    pub async fn upload_blob(&self, hash: &str, data: Vec<u8>) -> Result<()> {
+        let path = format!("{}/data/{}", self.config.user_id, hash);
+
+        let exists = self
+            .with_retry(|| async {
+                match self.config.storage_operator.stat(&path).await {
+                    Ok(_) => Ok(true),
+                    Err(e) if e.kind() == opendal::ErrorKind::NotFound => Ok(false),
+                    Err(e) => Err(e),
+                }
+            })
+            .await?;
+        if exists {
+            return Ok(());
         }
 
+        self.write_with_retry(&path, data)
+            .await
+            .context("Failed to upload blob")
+    }
+
+    // 写入对象，失败时按配置重试
+    async fn write_with_retry(&self, path: &str, data: Vec<u8>) -> Result<()> {
+        let data = opendal::Buffer::from(data);
+        self.with_retry(|| self.config.storage_operator.write(path, data.clone())).await?;
+        Ok(())
+    }
+
+    // 执行对象存储请求，每次请求受 timeout_seconds 限制；
+    // 失败后按指数退避重试 retry_attempts 次，仍然失败时返回最后一次的错误
+    async fn with_retry<T, F, Fut>(&self, request: F) -> Result<T>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = opendal::Result<T>>,
+    {
+        let timeout = Duration::from_secs(self.config.timeout_seconds);
+        let mut delay = INITIAL_RETRY_DELAY;
+        let mut attempt = 0;
+
+        loop {
+            let error = match tokio::time::timeout(timeout, request()).await {
+                Ok(Ok(value)) => return Ok(value),
+                Ok(Err(e)) => anyhow::Error::from(e),
+                Err(_) => anyhow!("请求超时（{} 秒）", self.config.timeout_seconds),
+            };
+
+            if attempt >= self.config.retry_attempts {
+                return Err(error);
+            }
+            attempt += 1;
+            tracing::warn!("对象存储请求失败，{:?} 后第 {} 次重试: {:#}", delay, attempt, error);
+            tokio::time::sleep(delay).await;
+            delay = (delay * 2).min(MAX_RETRY_DELAY);
+        }
+    }
+
+    /// 从 data/ 目录下载二进制数据
+    pub async fn download_blob(&self, hash: &str) -> Result<Vec<u8>> {
+        let path = format!("{}/data/{}", self.config.user_id, hash);
+        let data = self
+            .with_retry(|| self.config.storage_operator.read(&path))
+            .await
+            .context("Failed to download blob")?;
+        Ok(data.to_vec())
+    }
+
+    // 下载变化中的项目引用、本地还没有的 blob，然后交给处理函数
+    async fn publish_delta(&self, delta: SyncDelta) {
+        if delta.is_empty() {
+            return;
+        }
+
+        for item in &delta.added {
+            for hash in ClipboardItem::from(item).blob_hashes() {
+                if self.config.blobs.path(&hash).exists() {
+                    continue;
+                }
+                // 下载失败时项目照常写入，只是暂时无法粘贴图片或恢复文件
+                match self.download_blob(&hash).await {
+                    Ok(data) => {
+                        if let Err(e) = self.config.blobs.put_with_hash(&hash, &data) {
+                            tracing::warn!("保存 blob {} 失败: {}", hash, e);
+                        }
+                    }
+                    Err(e) => tracing::warn!("下载 blob {} 失败: {:#}", hash, e),
+                }
+            }
+        }
+
+        if let Some(handler) = self.remote_handler.lock().unwrap().as_ref() {
+            handler(delta);
+        }
+    }
+
+    /// 下载片段库，远端还没有片段库时返回空列表
+    pub async fn download_snippets(&self) -> Result<Vec<Snippet>> {
+        let path = format!("{}/snippets/library.json", self.config.user_id);
+        match self.config.storage_operator.read(&path).await {
+            Ok(data) => serde_json::from_slice(data.to_bytes().as_ref()).context("Failed to parse remote snippets"),
+            Err(e) if e.kind() == opendal::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// 上传片段库（包括已删除片段的墓碑）
+    pub async fn upload_snippets(&self, snippets: &[Snippet]) -> Result<()> {
+        let path = format!("{}/snippets/library.json", self.config.user_id);
+        let data = serde_json::to_vec(snippets)?;
+        self.config.storage_operator
+            .write(&path, data)
+            .await
+            .context("Failed to upload snippets")?;
         Ok(())
     }
 
@@ -196,15 +699,20 @@ impl SyncEngine {
 
     /// 获取同步状态（前端调用）
     pub async fn get_status(&self) -> Result<serde_json::Value> {
+        // 先锁发件箱再锁同步状态，与写入本地操作的顺序一致
+        let outbox = self.outbox.lock().await;
         let state = self.state.read().await;
-        let is_syncing = *self.is_syncing.lock().await;
+        // 同步期间一直持有 is_syncing 锁，查询状态时不等待
+        let is_syncing = self.is_syncing.try_lock().is_err();
         
         Ok(serde_json::json!({
             "item_count": state.items.len(),
             "is_syncing": is_syncing,
             "initialized": true,
             "last_sync": state.last_sync_timestamp,
-            "pending_ops": state.pending_ops.len()
+            "last_snapshot": state.snapshot_timestamp,
+            "outbox_depth": outbox.len(),
+            "last_upload_error": outbox.last_error()
         }))
     }
 
@@ -212,9 +720,17 @@ impl SyncEngine {
     pub async fn sync(&self) -> Result<()> {
         // 防止并发同步
         let _lock = self.is_syncing.lock().await;
+        if self.is_stopped() {
+            return Ok(());
+        }
 
         tracing::info!("开始同步");
 
+        // 0. 先上传发件箱中积压的操作；上传失败不影响拉取远端操作
+        if let Err(e) = self.flush_outbox().await {
+            tracing::warn!("上传发件箱失败，稍后重试: {:#}", e);
+        }
+
         // 1. 首次同步：加载快照
         if self.state.read().await.last_sync_timestamp.is_none() {
             self.initial_sync().await?;
@@ -231,20 +747,13 @@ impl SyncEngine {
     async fn initial_sync(&self) -> Result<()> {
         tracing::info!("执行首次同步");
 
-        // 1. 获取最新快照
-        let snapshot = self.load_latest_snapshot().await?;
-
-        // 2. 应用快照到本地状态
-        if let Some(snapshot) = snapshot {
-            let mut state = self.state.write().await;
-            state.items.clear();
-            for item in snapshot.items {
-                state.items.insert(item.id.clone(), item);
-            }
-            state.last_sync_timestamp = Some(snapshot.last_op_timestamp);
+        // 1. 获取最新快照并合并到本地状态
+        match self.load_snapshot_info().await? {
+            Some(info) => self.apply_snapshot(&info).await?,
+            None => tracing::info!("未找到快照，将从头开始同步"),
         }
 
-        // 3. 拉取快照之后的增量操作
+        // 2. 拉取快照之后的增量操作
         self.incremental_sync().await?;
 
         Ok(())
@@ -252,187 +761,547 @@ impl SyncEngine {
 
     /// 增量同步：拉取并应用新的操作日志
     async fn incremental_sync(&self) -> Result<()> {
-        let last_sync_time = self.state.read().await.last_sync_timestamp;
-
         // 列出远端操作日志
-        let ops = self.fetch_operations_since(last_sync_time).await?;
-
-        if ops.is_empty() {
-            return Ok(());
-        }
+        let (ops, legacy_ids) = self.fetch_new_operations().await?;
+        let changed = !ops.is_empty() || !legacy_ids.is_empty();
 
-        tracing::info!("发现 {} 个新操作", ops.len());
+        if !ops.is_empty() {
+            tracing::info!("发现 {} 个新操作", ops.len());
 
-        // 应用操作到本地状态（LWW 冲突解决）
-        self.apply_operations(ops).await?;
+            // 应用操作到本地状态（LWW 冲突解决），再把变化交给应用
+            let delta = self.apply_operations(ops).await?;
+            self.publish_delta(delta).await;
+        }
 
+        let mut state = self.state.write().await;
+        let first_sync = state.last_sync_timestamp.is_none();
+        state.legacy_applied.extend(legacy_ids);
+        state.last_sync_timestamp = Some(Utc::now());
+        // 没有新操作时状态只有同步时间变化，不必每次写入
+        if changed || first_sync {
+            state.save(&self.config.state_path)?;
+        }
         Ok(())
     }
 
-    /// 从对象存储获取最新快照
-    async fn load_latest_snapshot(&self) -> Result<Option<Snapshot>> {
+    /// 读取 latest.json，还没有快照时返回 None
+    async fn load_snapshot_info(&self) -> Result<Option<SnapshotInfo>> {
         let latest_path = format!("{}/snapshots/latest.json", self.config.user_id);
-        
-        match self.config.storage_operator.read(&latest_path).await {
-            Ok(data) => {
-                let snapshot_info: serde_json::Value = serde_json::from_slice(data.to_bytes().as_ref())?;
-                let snapshot_path = snapshot_info["snapshot_path"].as_str()
-                    .context("Invalid snapshot info")?;
-                
-                let snapshot_data = self.config.storage_operator.read(snapshot_path).await?;
-                let snapshot: Snapshot = serde_json::from_slice(snapshot_data.to_bytes().as_ref())?;
-                
-                tracing::info!("加载快照，包含 {} 个项目", snapshot.items.len());
-                Ok(Some(snapshot))
-            }
-            Err(e) if e.kind() == opendal::ErrorKind::NotFound => {
-                tracing::info!("未找到快照，将从头开始同步");
-                Ok(None)
+        let result = self
+            .with_retry(|| async {
+                match self.config.storage_operator.read(&latest_path).await {
+                    Ok(data) => Ok(Some(data)),
+                    Err(e) if e.kind() == opendal::ErrorKind::NotFound => Ok(None),
+                    Err(e) => Err(e),
+                }
+            })
+            .await?;
+        match result {
+            Some(data) => Ok(Some(serde_json::from_slice(&data.to_vec()).context("Invalid snapshot info")?)),
+            None => Ok(None),
+        }
+    }
+
+    // 下载快照并合并到本地状态，再把变化交给应用
+    async fn apply_snapshot(&self, info: &SnapshotInfo) -> Result<()> {
+        let data = self
+            .with_retry(|| self.config.storage_operator.read(&info.snapshot_path))
+            .await?;
+        let snapshot: Snapshot = serde_json::from_slice(&data.to_vec())
+            .context("Failed to parse snapshot")?;
+        tracing::info!("加载快照，包含 {} 个项目", snapshot.items.len());
+
+        let mut delta = SyncDelta::default();
+        {
+            let mut state = self.state.write().await;
+            state.merge_snapshot(snapshot, &mut delta);
+            if state.snapshot_timestamp.is_none_or(|timestamp| info.timestamp > timestamp) {
+                state.snapshot_timestamp = Some(info.timestamp);
+                state.ops_since_snapshot = 0;
             }
-            Err(e) => Err(e.into()),
+            state.save(&self.config.state_path)?;
         }
+        self.publish_delta(delta).await;
+        Ok(())
     }
 
-    /// 获取指定时间之后的操作日志
-    async fn fetch_operations_since(&self, since: Option<DateTime<Utc>>) -> Result<Vec<Operation>> {
+    /// 获取每个设备游标之后的操作日志，同时返回其中旧版本操作的 op_id
+    ///
+    /// 同一设备的操作按时钟顺序上传，游标之前的操作都已应用；按设备而不是按墙上时间过滤，
+    /// 时钟偏慢的设备晚上传的操作也不会漏掉。只下载新的操作，同步开销和历史长度无关。
+    async fn fetch_new_operations(&self) -> Result<(Vec<Operation>, Vec<String>)> {
+        let (cursors, legacy_applied) = {
+            let state = self.state.read().await;
+            (state.cursors.clone(), state.legacy_applied.clone())
+        };
         let oplog_path = format!("{}/oplog/", self.config.user_id);
-        
-        // 列出所有操作文件
+
+        // oplog/ 下是每个设备的目录，以及旧版本直接保存的操作文件
         let mut ops = Vec::new();
-        let entries = self.config.storage_operator
-            .list(&oplog_path)
+        let mut legacy_ids = Vec::new();
+        let entries = self
+            .with_retry(|| self.config.storage_operator.list(&oplog_path))
             .await?;
 
         for entry in entries {
-            let op_data = self.config.storage_operator
-                .read(&entry.path())
-                .await?;
-            
-            let op: Operation = serde_json::from_slice(op_data.to_bytes().as_ref())?;
-            
-            // 过滤出指定时间之后的操作
-            if let Some(since_time) = since {
-                if op.timestamp <= since_time {
+            if entry.path() == oplog_path {
+                continue;
+            }
+            if entry.metadata().is_dir() {
+                let device_id = entry.name().trim_end_matches('/');
+                let cursor = cursors.get(device_id).copied();
+                ops.extend(self.fetch_device_operations(entry.path(), cursor).await?);
+            } else if let Some(op_id) = entry.name().strip_suffix(".json") {
+                if legacy_applied.contains(op_id) {
                     continue;
                 }
+                ops.extend(self.read_operations(entry.path()).await?);
+                legacy_ids.push(op_id.to_string());
             }
-            
-            ops.push(op);
         }
 
-        // 按时间戳排序
-        ops.sort_by(|a, b| {
-            a.timestamp.cmp(&b.timestamp)
-                .then_with(|| a.device_id.cmp(&b.device_id))
-        });
+        // 按版本排序
+        ops.sort_by_cached_key(Operation::version);
 
+        Ok((ops, legacy_ids))
+    }
+
+    // 列出一个设备游标之后的操作；不支持 start_after 的存储（如本地文件系统）
+    // 会忽略该参数而列出全部对象，因此仍按文件名中的时钟过滤，只是列表开销较大。
+    // 对象可以是单个操作（.json）或操作段（.jsonl），文件名中的时钟是其中最后一个操作的时钟
+    async fn fetch_device_operations(&self, dir: &str, cursor: Option<Hlc>) -> Result<Vec<Operation>> {
+        let start_after = cursor.map(|cursor| format!("{}{}", dir, cursor));
+        let entries = self
+            .with_retry(|| async {
+                let list = self.config.storage_operator.list_with(dir);
+                match &start_after {
+                    Some(start_after) => list.start_after(start_after).await,
+                    None => list.await,
+                }
+            })
+            .await?;
+
+        let mut ops = Vec::new();
+        for entry in entries {
+            let Some(clock) = object_clock(entry.name()) else {
+                continue;
+            };
+            if cursor.is_some_and(|cursor| clock <= cursor) {
+                continue;
+            }
+            // 上传超时后重试时，同一个操作可能出现在两个段中
+            let new_ops = self.read_operations(entry.path()).await?;
+            ops.extend(new_ops.into_iter().filter(|op| cursor.is_none_or(|cursor| op.clock() > cursor)));
+        }
         Ok(ops)
     }
 
-    /// 应用操作到本地状态，实现 LWW 冲突解决
-    async fn apply_operations(&self, ops: Vec<Operation>) -> Result<()> {
+    // 读取单个操作文件或操作段中的所有操作
+    async fn read_operations(&self, path: &str) -> Result<Vec<Operation>> {
+        let data = self
+            .with_retry(|| self.config.storage_operator.read(path))
+            .await?
+            .to_vec();
+
+        if !path.ends_with(".jsonl") {
+            let op = serde_json::from_slice(&data)
+                .with_context(|| format!("Failed to parse operation {}", path))?;
+            return Ok(vec![op]);
+        }
+        data.split(|byte| *byte == b'\n')
+            .filter(|line| !line.iter().all(u8::is_ascii_whitespace))
+            .map(|line| {
+                serde_json::from_slice(line)
+                    .with_context(|| format!("Failed to parse operation segment {}", path))
+            })
+            .collect()
+    }
+
+    /// 应用操作到本地状态，实现 LWW 冲突解决；返回其他设备的操作造成的变化
+    async fn apply_operations(&self, ops: Vec<Operation>) -> Result<SyncDelta> {
         let mut state = self.state.write().await;
-        let mut latest_timestamp = state.last_sync_timestamp;
+        let mut delta = SyncDelta::default();
+        let mut latest_clock = Hlc::default();
+        state.ops_since_snapshot += ops.len();
 
         for op in ops {
-            match op.op_type {
-                OpType::Add => {
-                    if let Some(item) = &op.payload {
-                        // 检查是否存在冲突
-                        if let Some(existing_item) = state.items.get(&op.target_id) {
-                            // 需要比较时间戳来决定保留哪个版本
-                            // 这里简化处理，假设较新的时间戳获胜
-                            if item.created_at >= existing_item.created_at {
-                                state.items.insert(op.target_id.clone(), item.clone());
-                            }
-                        } else {
-                            state.items.insert(op.target_id.clone(), item.clone());
-                        }
-                    }
-                }
-                OpType::Delete => {
-                    // 检查删除操作是否应该被应用
-                    if let Some(existing_item) = state.items.get(&op.target_id) {
-                        // 如果删除操作的时间戳晚于项目的创建时间，则删除
-                        if op.timestamp >= existing_item.created_at {
-                            state.items.remove(&op.target_id);
-                        }
-                    }
-                }
+            // 本机的操作已经在本地应用过，不再传回本机
+            if op.device_id == self.config.device_id {
+                state.apply(&op, &mut SyncDelta::default());
+            } else {
+                state.apply(&op, &mut delta);
             }
 
-            // 更新最后同步时间戳
-            if latest_timestamp.is_none() || op.timestamp > latest_timestamp.unwrap() {
-                latest_timestamp = Some(op.timestamp);
-            }
+            // 更新该设备的游标
+            let clock = op.clock();
+            let cursor = state.cursors.entry(op.device_id.clone()).or_default();
+            *cursor = (*cursor).max(clock);
+            latest_clock = latest_clock.max(clock);
+        }
+
+        // 之后本机产生的操作排在所有已见过的操作之后
+        self.clock.lock().unwrap().observe(latest_clock)?;
+        Ok(delta)
+    }
+
+    /// 后台维护：本地进度落后于已清理的操作日志时重新加载快照；
+    /// 需要时由持有租约的设备生成快照，并清理旧快照和已被快照包含的操作日志
+    pub async fn maintain(&self) -> Result<()> {
+        // 和同步互斥，避免同时修改本地状态
+        let _lock = self.is_syncing.lock().await;
+        if self.is_stopped() {
+            return Ok(());
+        }
+
+        let info = self.load_snapshot_info().await?;
+        if let Some(info) = &info {
+            self.catch_up(info).await?;
         }
 
-        state.last_sync_timestamp = latest_timestamp;
+        if !self.snapshot_due(info.as_ref()).await {
+            return Ok(());
+        }
+        let Some(mut lease) = self.acquire_lease().await? else {
+            tracing::info!("其他设备正在生成快照");
+            return Ok(());
+        };
+
+        let result = self.snapshot_and_prune(info, &mut lease).await;
+        if let Err(e) = self.release_lease(&lease).await {
+            tracing::warn!("释放快照租约失败: {:#}", e);
+        }
+        result
+    }
+
+    // 其他设备生成了新快照时重新开始计数；已清理的操作中有本机还没应用的，合并快照补上
+    async fn catch_up(&self, info: &SnapshotInfo) -> Result<()> {
+        let behind = {
+            let mut state = self.state.write().await;
+            if state.snapshot_timestamp.is_none_or(|timestamp| info.timestamp > timestamp) {
+                state.snapshot_timestamp = Some(info.timestamp);
+                state.ops_since_snapshot = 0;
+            }
+            info.pruned
+                .iter()
+                .any(|(device_id, pruned)| state.cursors.get(device_id).is_none_or(|cursor| cursor < pruned))
+        };
+
+        if behind {
+            tracing::warn!("本地同步进度落后于已清理的操作日志，重新加载快照");
+            self.apply_snapshot(info).await?;
+        }
         Ok(())
     }
 
-    /// 生成快照（通常由后台任务调用）
-    pub async fn create_snapshot(&self) -> Result<()> {
-        let state = self.state.read().await;
-        
-        let snapshot = Snapshot {
-            items: state.items.values().cloned().collect(),
-            snapshot_timestamp: Utc::now(),
-            last_op_timestamp: state.last_sync_timestamp.unwrap_or_else(Utc::now),
-            device_id: self.config.device_id.clone(),
+    // 累计操作达到 snapshot_every_ops，或距上次快照超过 snapshot_interval_seconds 且有新操作
+    async fn snapshot_due(&self, info: Option<&SnapshotInfo>) -> bool {
+        let ops = self.state.read().await.ops_since_snapshot;
+        let interval = chrono::Duration::seconds(self.config.snapshot_interval_seconds as i64);
+        ops >= self.config.snapshot_every_ops
+            || (ops > 0 && info.is_none_or(|info| Utc::now() - info.timestamp >= interval))
+    }
+
+    // 每次修改 latest.json 和删除对象之前都确认租约仍由本机持有，租约失效时中止
+    async fn snapshot_and_prune(&self, previous: Option<SnapshotInfo>, lease: &mut SnapshotLease) -> Result<()> {
+        let pruned = previous.map(|info| info.pruned).unwrap_or_default();
+        let mut info = self.create_snapshot(pruned, lease).await?;
+        self.prune_snapshots(&info, lease).await?;
+        self.prune_oplog(&mut info, lease).await
+    }
+
+    // 生成快照并更新 latest.json，pruned 沿用上一个快照的清理进度
+    //
+    // 本地状态包含发件箱中的操作，先上传发件箱，快照中才不会出现其他设备还拿不到操作和 blob 的项目
+    async fn create_snapshot(&self, pruned: HashMap<String, Hlc>, lease: &mut SnapshotLease) -> Result<SnapshotInfo> {
+        self.flush_outbox().await.context("快照前上传发件箱失败")?;
+        let snapshot = {
+            // 持有发件箱锁读取状态，期间不会有新的本地操作写入状态
+            let outbox = self.outbox.lock().await;
+            ensure!(outbox.len() == 0, "发件箱中还有未上传的操作，稍后再生成快照");
+            let state = self.state.read().await;
+            Snapshot {
+                items: state.items.values().cloned().collect(),
+                snapshot_timestamp: Utc::now(),
+                last_op_timestamp: state.last_sync_timestamp.unwrap_or_else(Utc::now),
+                device_id: self.config.device_id.clone(),
+                versions: state.versions.clone(),
+                cursors: state.cursors.clone(),
+            }
         };
 
         let timestamp_str = snapshot.snapshot_timestamp.format("%Y%m%d_%H%M%S").to_string();
         let snapshot_path = format!("{}/snapshots/{}_snapshot.json", self.config.user_id, timestamp_str);
         
         // 上传快照
-        let snapshot_data = serde_json::to_vec(&snapshot)?;
-        self.config.storage_operator
-            .write(&snapshot_path, snapshot_data)
-            .await?;
+        self.write_with_retry(&snapshot_path, serde_json::to_vec(&snapshot)?)
+            .await
+            .context("Failed to upload snapshot")?;
 
         // 更新 latest.json
-        let latest_info = serde_json::json!({
-            "snapshot_path": snapshot_path,
-            "timestamp": snapshot.snapshot_timestamp
-        });
+        let info = SnapshotInfo {
+            snapshot_path: snapshot_path.clone(),
+            timestamp: snapshot.snapshot_timestamp,
+            cursors: snapshot.cursors,
+            pruned,
+        };
+        self.check_lease(lease).await?;
+        self.write_snapshot_info(&info).await?;
+
+        {
+            let mut state = self.state.write().await;
+            state.snapshot_timestamp = Some(info.timestamp);
+            state.ops_since_snapshot = 0;
+            state.save(&self.config.state_path)?;
+        }
+
+        tracing::info!("快照已创建: {}", snapshot_path);
+        Ok(info)
+    }
+
+    async fn write_snapshot_info(&self, info: &SnapshotInfo) -> Result<()> {
         let latest_path = format!("{}/snapshots/latest.json", self.config.user_id);
-        self.config.storage_operator
-            .write(&latest_path, serde_json::to_vec(&latest_info)?)
+        self.write_with_retry(&latest_path, serde_json::to_vec(info)?)
+            .await
+            .context("Failed to update latest snapshot")
+    }
+
+    // 只保留最近的 snapshot_keep 个快照；latest.json 指向的快照总是保留
+    async fn prune_snapshots(&self, info: &SnapshotInfo, lease: &mut SnapshotLease) -> Result<()> {
+        let snapshots_path = format!("{}/snapshots/", self.config.user_id);
+        let entries = self
+            .with_retry(|| self.config.storage_operator.list(&snapshots_path))
             .await?;
 
-        tracing::info!("快照已创建: {}", snapshot_path);
+        // 文件名以生成时间开头，按名称排序即按时间排序
+        let mut paths: Vec<String> = entries
+            .iter()
+            .filter(|entry| entry.name().ends_with("_snapshot.json"))
+            .map(|entry| entry.path().to_string())
+            .collect();
+        paths.sort();
+
+        let expired = paths.len().saturating_sub(self.config.snapshot_keep.max(1));
+        let expired: Vec<String> = paths.into_iter().take(expired).filter(|path| *path != info.snapshot_path).collect();
+        self.delete_objects(&expired, lease).await?;
+        for path in &expired {
+            tracing::info!("已删除旧快照: {}", path);
+        }
+        Ok(())
+    }
+
+    // 删除已被快照包含、且早于保留时间的操作日志；
+    // 先在 latest.json 中记录清理进度再删除，其他设备据此判断是否需要重新加载快照
+    async fn prune_oplog(&self, info: &mut SnapshotInfo, lease: &mut SnapshotLease) -> Result<()> {
+        let retention = chrono::Duration::seconds(self.config.oplog_retention_seconds as i64);
+        let horizon = Hlc::from(Utc::now() - retention);
+        // 每个设备可以清理的最后一个时钟
+        let limit = |device_id: &str| info.cursors.get(device_id).map(|cursor| (*cursor).min(horizon));
+
+        let oplog_path = format!("{}/oplog/", self.config.user_id);
+        let entries = self
+            .with_retry(|| self.config.storage_operator.list(&oplog_path))
+            .await?;
+
+        let mut prunable: Vec<(String, String, Hlc)> = Vec::new();
+        for entry in entries {
+            if entry.path() == oplog_path {
+                continue;
+            }
+            if entry.metadata().is_dir() {
+                let device_id = entry.name().trim_end_matches('/');
+                let Some(limit) = limit(device_id) else {
+                    continue;
+                };
+                let objects = self
+                    .with_retry(|| self.config.storage_operator.list(entry.path()))
+                    .await?;
+                for object in objects {
+                    if let Some(clock) = object_clock(object.name()).filter(|clock| *clock <= limit) {
+                        prunable.push((object.path().to_string(), device_id.to_string(), clock));
+                    }
+                }
+            } else if entry.name().ends_with(".json") {
+                // 旧版本的操作文件名中没有时钟，需要读取后判断
+                for op in self.read_operations(entry.path()).await? {
+                    if limit(&op.device_id).is_some_and(|limit| op.clock() <= limit) {
+                        prunable.push((entry.path().to_string(), op.device_id.clone(), op.clock()));
+                    }
+                }
+            }
+        }
+
+        if prunable.is_empty() {
+            return Ok(());
+        }
+        for (_, device_id, clock) in &prunable {
+            let pruned = info.pruned.entry(device_id.clone()).or_default();
+            *pruned = (*pruned).max(*clock);
+        }
+        self.check_lease(lease).await?;
+        self.write_snapshot_info(info).await?;
+
+        let paths: Vec<String> = prunable.into_iter().map(|(path, _, _)| path).collect();
+        self.delete_objects(&paths, lease).await?;
+        tracing::info!("已清理 {} 个操作日志对象", paths.len());
         Ok(())
     }
 
+    // 分批删除对象，每批之前确认租约
+    async fn delete_objects(&self, paths: &[String], lease: &mut SnapshotLease) -> Result<()> {
+        for batch in paths.chunks(PRUNE_BATCH_SIZE) {
+            self.check_lease(lease).await?;
+            for path in batch {
+                self.with_retry(|| self.config.storage_operator.delete(path)).await?;
+            }
+        }
+        Ok(())
+    }
+
+    // 获取快照租约：租约不存在、已过期或属于本机时写入新的租约，
+    // 等待片刻后再读取确认，多个设备同时写入时只有最后写入的设备获得租约。
+    // 目前使用的 opendal 版本不支持条件写入，因此靠随机令牌确认租约没有被改写，
+    // 并在之后每一步修改之前重新确认
+    async fn acquire_lease(&self) -> Result<Option<SnapshotLease>> {
+        if let Some(lease) = self.read_lease().await? {
+            if lease.device_id != self.config.device_id && lease.expires_at > Utc::now() {
+                return Ok(None);
+            }
+        }
+
+        let lease = SnapshotLease {
+            device_id: self.config.device_id.clone(),
+            expires_at: Utc::now() + chrono::Duration::seconds(LEASE_SECONDS),
+            token: Uuid::new_v4().to_string(),
+        };
+        self.write_lease(&lease).await?;
+
+        tokio::time::sleep(LEASE_SETTLE_DELAY).await;
+        let confirmed = self.read_lease().await?.is_some_and(|current| current.token == lease.token);
+        Ok(confirmed.then_some(lease))
+    }
+
+    // 确认租约仍由本机持有且没有临近过期；剩余时间不到一半时续期
+    async fn check_lease(&self, lease: &mut SnapshotLease) -> Result<()> {
+        let current = self.read_lease().await?;
+        ensure!(
+            current.is_some_and(|current| current.token == lease.token),
+            "快照租约已被其他设备获取，中止快照和清理"
+        );
+        let now = Utc::now();
+        ensure!(
+            lease.expires_at - chrono::Duration::seconds(LEASE_MARGIN_SECONDS) > now,
+            "快照租约即将过期，中止快照和清理"
+        );
+
+        if lease.expires_at - now < chrono::Duration::seconds(LEASE_SECONDS / 2) {
+            lease.expires_at = now + chrono::Duration::seconds(LEASE_SECONDS);
+            self.write_lease(lease).await?;
+        }
+        Ok(())
+    }
+
+    async fn write_lease(&self, lease: &SnapshotLease) -> Result<()> {
+        self.write_with_retry(&self.lease_path(), serde_json::to_vec(lease)?)
+            .await
+            .context("Failed to write snapshot lease")
+    }
+
+    async fn release_lease(&self, lease: &SnapshotLease) -> Result<()> {
+        if self.read_lease().await?.is_some_and(|current| current.token == lease.token) {
+            let path = self.lease_path();
+            self.with_retry(|| self.config.storage_operator.delete(&path)).await?;
+        }
+        Ok(())
+    }
+
+    async fn read_lease(&self) -> Result<Option<SnapshotLease>> {
+        let path = self.lease_path();
+        let result = self
+            .with_retry(|| async {
+                match self.config.storage_operator.read(&path).await {
+                    Ok(data) => Ok(Some(data)),
+                    Err(e) if e.kind() == opendal::ErrorKind::NotFound => Ok(None),
+                    Err(e) => Err(e),
+                }
+            })
+            .await?;
+        // 租约损坏时视为没有租约
+        Ok(result.and_then(|data| serde_json::from_slice(&data.to_vec()).ok()))
+    }
+
+    fn lease_path(&self) -> String {
+        format!("{}/snapshots/lease.json", self.config.user_id)
+    }
+
     /// 启动后台同步任务
+    ///
+    /// 调用 [`SyncEngine::shutdown`] 后返回。
     pub async fn start_background_sync(&self) -> Result<()> {
         let interval = tokio::time::Duration::from_secs(self.config.sync_interval_seconds);
-        let mut timer = tokio::time::interval(interval);
+        let mut next_sync = tokio::time::Instant::now();
+        let mut last_maintenance = tokio::time::Instant::now();
+        let mut stopped = self.stopped.subscribe();
 
         loop {
-            timer.tick().await;
+            // 等到下一次同步的时间，期间引擎停止时立即退出
+            if tokio::time::timeout_at(next_sync, stopped.wait_for(|stopped| *stopped)).await.is_ok() {
+                return Ok(());
+            }
+            next_sync = tokio::time::Instant::now() + interval;
             
             if let Err(e) = self.sync().await {
                 tracing::error!("同步失败: {}", e);
+                continue;
+            }
+
+            // 定期维护；累计的操作达到快照阈值时提前维护
+            let ops = self.state.read().await.ops_since_snapshot;
+            if last_maintenance.elapsed() >= MAINTENANCE_INTERVAL || ops >= self.config.snapshot_every_ops {
+                last_maintenance = tokio::time::Instant::now();
+                if let Err(e) = self.maintain().await {
+                    tracing::warn!("同步维护失败: {:#}", e);
+                }
             }
         }
     }
 }
 
+// 操作段的对象名：{最后一个操作的时钟}_{第一个操作的 op_id}.jsonl。
+// 以最后一个时钟开头，游标之后的段按名称排序都在游标之后；
+// 重试上传同一批操作时对象名不变，不会产生重复的段
+fn segment_name(ops: &[&Operation]) -> String {
+    let last_clock = ops.iter().map(|op| op.clock()).max().unwrap_or_default();
+    let first_op_id = ops.first().map(|op| op.op_id.as_str()).unwrap_or_default();
+    format!("{}_{}.jsonl", last_clock, first_op_id)
+}
+
+// 从操作或操作段的对象名（{hlc}_{id}.json 或 .jsonl）中取出时钟
+fn object_clock(name: &str) -> Option<Hlc> {
+    name.split_once('_')?.0.parse().ok()
+}
+
 /// 辅助函数：从本地 ClipboardItem 转换为 SyncClipboardItem
 impl From<&crate::storage::ClipboardItem> for SyncClipboardItem {
     fn from(item: &crate::storage::ClipboardItem) -> Self {
         Self {
             id: item.id.clone(),
-            content_type: item.item_type.clone(),
+            // 识别出类型的文本使用 "text/url" 这样的细分类型
+            content_type: match &item.detected_kind {
+                Some(kind) => format!("{}/{}", item.item_type, kind.name()),
+                None => item.item_type.clone(),
+            },
             content: item.content.clone(),
             created_at: DateTime::from_timestamp(item.timestamp as i64, 0)
                 .unwrap_or_else(Utc::now),
             metadata: ItemMetadata {
                 source_device: "unknown".to_string(), // TODO: 从系统获取设备名
-                source_app: None,
-                content_hash: None,
+                source_app: item.source_app.clone(),
+                content_hash: item.image.as_ref().map(|image| image.hash.clone()),
+                image: item.image.clone(),
+                html: item.html.clone(),
+                rtf: item.rtf.clone(),
+                file_paths: item.file_paths.clone(),
+                file_types: item.file_types.clone(),
             },
         }
     }
@@ -441,14 +1310,354 @@ impl From<&crate::storage::ClipboardItem> for SyncClipboardItem {
 /// 辅助函数：从 SyncClipboardItem 转换为本地 ClipboardItem
 impl From<&SyncClipboardItem> for ClipboardItem {
     fn from(item: &SyncClipboardItem) -> Self {
+        // content_type 可能带有识别类型的后缀，识别结果在本地重新计算
+        let item_type = item.content_type.split('/').next().unwrap_or_default();
         Self {
             id: item.id.clone(),
             content: item.content.clone(),
             timestamp: item.created_at.timestamp() as u64,
-            item_type: item.content_type.clone(),
-            size: Some(item.content.len() as u64),
-            file_paths: None,
-            file_types: None,
+            item_type: item_type.to_string(),
+            size: Some(match &item.metadata.file_types {
+                Some(file_types) => file_types.iter().map(|info| info.size).sum(),
+                None => item.content.len() as u64,
+            }),
+            file_paths: item.metadata.file_paths.clone(),
+            file_types: item.metadata.file_types.clone(),
+            source_app: item.metadata.source_app.clone(),
+            image: item.metadata.image.clone(),
+            html: item.metadata.html.clone(),
+            rtf: item.metadata.rtf.clone(),
+            detected_kind: if item_type == "text" { classify(&item.content) } else { None },
+            ..Default::default()
         }
     }
-} 
\ No newline at end of file
+} 
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    pub(crate) fn memory_operator() -> Operator {
+        Operator::new(opendal::services::Memory::default()).unwrap().finish()
+    }
+
+    pub(crate) fn runtime() -> tokio::runtime::Runtime {
+        tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap()
+    }
+
+    /// 使用内存存储的引擎，本地文件放在 dir/device_id 下；合并窗口为 0，本地操作立即上传
+    pub(crate) fn memory_engine(dir: &Path, operator: &Operator, device_id: &str) -> SyncEngine {
+        let local_dir = dir.join(device_id);
+        SyncEngine::new(SyncConfig {
+            user_id: "user".to_string(),
+            device_id: device_id.to_string(),
+            storage_operator: operator.clone(),
+            sync_interval_seconds: 3600,
+            retry_attempts: 0,
+            timeout_seconds: 5,
+            outbox_path: local_dir.join("sync_outbox.json"),
+            clock_path: local_dir.join("sync_clock.json"),
+            state_path: local_dir.join("sync_state.json"),
+            segment_max_ops: 100,
+            segment_window_seconds: 0,
+            snapshot_every_ops: 500,
+            snapshot_interval_seconds: 6 * 3600,
+            snapshot_keep: 3,
+            oplog_retention_seconds: 0,
+            blobs: BlobStore::new(local_dir.join("blobs")).unwrap(),
+        })
+    }
+
+    /// 设备上传的所有操作段，按对象名排序
+    pub(crate) async fn device_segments(operator: &Operator, device_id: &str) -> Vec<Vec<Operation>> {
+        let mut entries = operator
+            .list_with(&format!("user/oplog/{}/", device_id))
+            .await
+            .unwrap_or_default();
+        entries.sort_by(|a, b| a.path().cmp(b.path()));
+
+        let mut segments = Vec::new();
+        for entry in entries.iter().filter(|entry| entry.name().ends_with(".jsonl")) {
+            let content = operator.read(entry.path()).await.unwrap().to_vec();
+            let ops = content
+                .split(|byte| *byte == b'\n')
+                .filter(|line| !line.is_empty())
+                .map(|line| serde_json::from_slice(line).unwrap())
+                .collect();
+            segments.push(ops);
+        }
+        segments
+    }
+
+    pub(crate) fn text_item(id: &str, content: &str) -> SyncClipboardItem {
+        SyncClipboardItem::from(&ClipboardItem {
+            id: id.to_string(),
+            item_type: "text".to_string(),
+            content: content.to_string(),
+            timestamp: 1_700_000_000,
+            ..Default::default()
+        })
+    }
+
+    mod convergence {
+        use super::*;
+        use proptest::prelude::*;
+
+        const ITEMS: &[&str] = &["a", "b", "c", "d"];
+        const DEVICES: &[&str] = &["device-a", "device-b", "device-c"];
+
+        // 时钟取值范围很小，不同设备经常产生相同的时钟，需要按设备和操作 ID 决出胜负
+        fn hlc() -> impl Strategy<Value = Hlc> {
+            (0i64..8, 0u32..3).prop_map(|(wall_ms, counter)| Hlc { wall_ms, counter })
+        }
+
+        fn operation() -> impl Strategy<Value = Operation> {
+            let device = prop::sample::select(DEVICES).prop_map(str::to_string);
+            let item = prop::sample::select(ITEMS);
+            prop_oneof![
+                (item.clone(), device.clone(), hlc(), "[a-z]{1,8}").prop_map(|(id, device, hlc, content)| {
+                    Operation::new_add(text_item(id, &content), device, hlc)
+                }),
+                (item, device.clone(), hlc()).prop_map(|(id, device, hlc)| {
+                    Operation::new_delete(id.to_string(), device, hlc)
+                }),
+                (prop::sample::subsequence(ITEMS, 1..=ITEMS.len()), device, hlc()).prop_map(|(ids, device, hlc)| {
+                    Operation::new_delete_batch(ids.into_iter().map(str::to_string).collect(), device, hlc)
+                }),
+            ]
+        }
+
+        // 一组操作，以及两个带重复操作的任意排列
+        fn orderings() -> impl Strategy<Value = (Vec<Operation>, Vec<Operation>, Vec<Operation>)> {
+            prop::collection::vec(operation(), 1..24).prop_flat_map(|ops| {
+                let len = ops.len();
+                let ordering = move || {
+                    prop::collection::vec(0..len, 0..len).prop_flat_map(move |duplicates| {
+                        let indices: Vec<usize> = (0..len).chain(duplicates).collect();
+                        Just(indices).prop_shuffle()
+                    })
+                };
+                (Just(ops), ordering(), ordering())
+            })
+            .prop_map(|(ops, first, second)| {
+                let pick = |indices: Vec<usize>| indices.into_iter().map(|index| ops[index].clone()).collect();
+                (ops.clone(), pick(first), pick(second))
+            })
+        }
+
+        fn apply_all(state: &mut SyncState, ops: &[Operation]) -> SyncDelta {
+            let mut delta = SyncDelta::default();
+            for op in ops {
+                state.apply(op, &mut delta);
+            }
+            delta
+        }
+
+        fn snapshot_of(state: &SyncState) -> (serde_json::Value, serde_json::Value) {
+            (serde_json::to_value(&state.items).unwrap(), serde_json::to_value(&state.versions).unwrap())
+        }
+
+        proptest! {
+            #[test]
+            fn any_ordering_converges((ops, first, second) in orderings()) {
+                let mut in_order = SyncState::default();
+                let mut shuffled = SyncState::default();
+                let mut duplicated = SyncState::default();
+                apply_all(&mut in_order, &ops);
+                apply_all(&mut shuffled, &first);
+                apply_all(&mut duplicated, &second);
+
+                prop_assert_eq!(snapshot_of(&in_order), snapshot_of(&shuffled));
+                prop_assert_eq!(snapshot_of(&in_order), snapshot_of(&duplicated));
+            }
+
+            #[test]
+            fn applying_again_is_a_no_op((ops, first, _second) in orderings()) {
+                let mut state = SyncState::default();
+                apply_all(&mut state, &ops);
+                let before = snapshot_of(&state);
+
+                let delta = apply_all(&mut state, &first);
+                prop_assert!(delta.added.is_empty() && delta.removed.is_empty());
+                prop_assert_eq!(before, snapshot_of(&state));
+            }
+
+            #[test]
+            fn newest_operation_wins((ops, first, _second) in orderings()) {
+                let mut state = SyncState::default();
+                apply_all(&mut state, &first);
+
+                // 每个项目的结果由版本最大的操作决定，较早的 ADD 晚到也不会让删除的项目复活
+                for id in ITEMS {
+                    let winner = ops.iter().filter(|op| op.targets().contains(id)).max_by_key(|op| op.version());
+                    match winner {
+                        Some(op) if op.op_type == OpType::Add => {
+                            let content = &op.payload.as_ref().unwrap().content;
+                            prop_assert_eq!(state.items.get(*id).map(|item| &item.content), Some(content));
+                        }
+                        Some(_) => prop_assert!(!state.items.contains_key(*id)),
+                        None => prop_assert!(!state.versions.contains_key(*id)),
+                    }
+                    prop_assert_eq!(state.versions.get(*id).cloned(), winner.map(|op| op.version()));
+                }
+            }
+        }
+
+        #[test]
+        fn late_add_does_not_resurrect_deleted_item() {
+            let add = Operation::new_add(text_item("a", "old"), "device-a".to_string(), Hlc { wall_ms: 1, counter: 0 });
+            let delete = Operation::new_delete_batch(vec!["a".to_string(), "b".to_string()], "device-b".to_string(), Hlc { wall_ms: 2, counter: 0 });
+
+            let mut state = SyncState::default();
+            apply_all(&mut state, &[delete.clone()]);
+            let delta = apply_all(&mut state, &[add.clone(), add]);
+            assert!(delta.added.is_empty());
+            assert!(state.items.is_empty());
+            assert_eq!(state.versions.get("a"), Some(&delete.version()));
+        }
+    }
+
+    #[test]
+    fn concurrent_local_operations_enqueue_in_clock_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let operator = memory_operator();
+        runtime().block_on(async {
+            let mut engine = memory_engine(dir.path(), &operator, "device-a");
+            engine.config.segment_max_ops = 7;
+            let engine = Arc::new(engine);
+
+            let tasks: Vec<_> = (0..40)
+                .map(|i| {
+                    let engine = engine.clone();
+                    tokio::spawn(async move {
+                        match i % 3 {
+                            0 => engine.local_delete(format!("item-{}", i)).await,
+                            _ => engine.local_add(text_item(&format!("item-{}", i), "text"), Vec::new()).await,
+                        }
+                    })
+                })
+                .collect();
+            for task in tasks {
+                task.await.unwrap().unwrap();
+            }
+
+            // 按段的对象名（即读取顺序）排列后，所有操作的时钟严格递增
+            let clocks: Vec<Hlc> = device_segments(&operator, "device-a")
+                .await
+                .iter()
+                .flatten()
+                .map(|op| op.clock())
+                .collect();
+            assert_eq!(clocks.len(), 40);
+            assert!(clocks.windows(2).all(|pair| pair[0] < pair[1]), "{:?}", clocks);
+        });
+    }
+
+    #[test]
+    fn shutdown_drains_outbox_before_files_are_reused() {
+        let dir = tempfile::tempdir().unwrap();
+        let operator = memory_operator();
+        runtime().block_on(async {
+            let mut engine = memory_engine(dir.path(), &operator, "device-a");
+            // 合并窗口很长，操作停留在发件箱中，由停止引擎时上传
+            engine.config.segment_window_seconds = 3600;
+            let engine = Arc::new(engine);
+
+            let background = tokio::spawn({
+                let engine = engine.clone();
+                async move { engine.start_background_sync().await }
+            });
+            // 等第一次后台同步结束后再产生本地操作
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            let pending = tokio::spawn({
+                let engine = engine.clone();
+                async move { engine.local_add(text_item("a", "hello"), Vec::new()).await }
+            });
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            assert!(device_segments(&operator, "device-a").await.is_empty());
+
+            engine.shutdown().await;
+            tokio::time::timeout(Duration::from_secs(5), background).await.unwrap().unwrap().unwrap();
+            assert_eq!(device_segments(&operator, "device-a").await.len(), 1);
+
+            // 停止后的本地操作被拒绝，不再修改发件箱
+            assert!(engine.local_delete("a".to_string()).await.is_err());
+            assert!(!pending.is_finished());
+
+            // 新的引擎从同一组文件继续：发件箱为空，本地状态包含已上传的项目
+            let reloaded = memory_engine(dir.path(), &operator, "device-a");
+            assert_eq!(reloaded.outbox.lock().await.len(), 0);
+            assert_eq!(reloaded.get_all_items().await.len(), 1);
+        });
+    }
+
+    fn lease(device_id: &str, token: &str) -> SnapshotLease {
+        SnapshotLease {
+            device_id: device_id.to_string(),
+            expires_at: Utc::now() + chrono::Duration::seconds(LEASE_SECONDS),
+            token: token.to_string(),
+        }
+    }
+
+    #[test]
+    fn lease_taken_over_aborts_before_latest_is_updated() {
+        let dir = tempfile::tempdir().unwrap();
+        let operator = memory_operator();
+        runtime().block_on(async {
+            let engine = memory_engine(dir.path(), &operator, "device-a");
+            engine.local_add(text_item("a", "hello"), Vec::new()).await.unwrap();
+
+            // 本机的租约过期后被其他设备获取
+            let mut ours = lease("device-a", "ours");
+            engine.write_lease(&lease("device-b", "theirs")).await.unwrap();
+            assert!(engine.snapshot_and_prune(None, &mut ours).await.is_err());
+            assert!(engine.load_snapshot_info().await.unwrap().is_none());
+            assert_eq!(device_segments(&operator, "device-a").await.len(), 1);
+            // 不会删除其他设备的租约
+            engine.release_lease(&ours).await.unwrap();
+            assert_eq!(engine.read_lease().await.unwrap().map(|lease| lease.token), Some("theirs".to_string()));
+
+            // 租约即将过期时同样中止
+            ours.expires_at = Utc::now() + chrono::Duration::seconds(LEASE_MARGIN_SECONDS / 2);
+            engine.write_lease(&ours).await.unwrap();
+            assert!(engine.snapshot_and_prune(None, &mut ours).await.is_err());
+            assert!(engine.load_snapshot_info().await.unwrap().is_none());
+
+            // 持有租约时正常生成快照
+            let mut ours = lease("device-a", "ours");
+            engine.write_lease(&ours).await.unwrap();
+            engine.snapshot_and_prune(None, &mut ours).await.unwrap();
+            assert!(engine.load_snapshot_info().await.unwrap().is_some());
+        });
+    }
+
+    #[test]
+    fn snapshot_uploads_outbox_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let operator = memory_operator();
+        runtime().block_on(async {
+            let mut engine = memory_engine(dir.path(), &operator, "device-a");
+            // 合并窗口很长，操作停留在发件箱中
+            engine.config.segment_window_seconds = 3600;
+            let engine = Arc::new(engine);
+            let pending = tokio::spawn({
+                let engine = engine.clone();
+                async move { engine.local_add(text_item("a", "hello"), Vec::new()).await }
+            });
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            assert!(device_segments(&operator, "device-a").await.is_empty());
+
+            let mut ours = lease("device-a", "ours");
+            engine.write_lease(&ours).await.unwrap();
+            let info = engine.create_snapshot(HashMap::new(), &mut ours).await.unwrap();
+
+            // 快照中的项目对应的操作已经上传
+            assert_eq!(device_segments(&operator, "device-a").await.len(), 1);
+            assert_eq!(engine.outbox.lock().await.len(), 0);
+            let content = operator.read(&info.snapshot_path).await.unwrap().to_vec();
+            let snapshot: Snapshot = serde_json::from_slice(&content).unwrap();
+            assert_eq!(snapshot.items.len(), 1);
+            pending.abort();
+        });
+    }
+}
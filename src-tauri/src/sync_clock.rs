@@ -0,0 +1,101 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// 混合逻辑时钟（HLC）的时间戳
+///
+/// 先比较物理时间（毫秒），相同时比较逻辑计数。本机产生的时间戳总是大于
+/// 本机之前产生或见过的所有时间戳，时钟偏慢的设备也不会一直输掉冲突。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Hlc {
+    pub wall_ms: i64,
+    pub counter: u32,
+}
+
+impl From<DateTime<Utc>> for Hlc {
+    /// 没有 HLC 的旧操作按墙上时间比较
+    fn from(timestamp: DateTime<Utc>) -> Self {
+        Self {
+            wall_ms: timestamp.timestamp_millis(),
+            counter: 0,
+        }
+    }
+}
+
+/// 定长的十进制格式，字典序和时钟顺序一致，用于对象名
+impl fmt::Display for Hlc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016}-{:010}", self.wall_ms, self.counter)
+    }
+}
+
+impl FromStr for Hlc {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (wall_ms, counter) = s.split_once('-').context("Invalid HLC")?;
+        Ok(Self {
+            wall_ms: wall_ms.parse().context("Invalid HLC")?,
+            counter: counter.parse().context("Invalid HLC")?,
+        })
+    }
+}
+
+/// 本设备的混合逻辑时钟，最后发出的时间戳保存在磁盘上，重启后不会倒退
+pub struct HybridClock {
+    path: PathBuf,
+    last: Hlc,
+}
+
+impl HybridClock {
+    /// 加载时钟状态，文件不存在或损坏时从当前时间开始
+    pub fn load(path: PathBuf) -> Self {
+        let last = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        Self { path, last }
+    }
+
+    /// 为本地操作生成新的时间戳
+    pub fn tick(&mut self) -> Result<Hlc> {
+        let wall_ms = Utc::now().timestamp_millis();
+        self.last = if wall_ms > self.last.wall_ms {
+            Hlc { wall_ms, counter: 0 }
+        } else {
+            Hlc {
+                wall_ms: self.last.wall_ms,
+                counter: self.last.counter + 1,
+            }
+        };
+        self.save()?;
+        Ok(self.last)
+    }
+
+    /// 收到其他设备的时间戳，之后生成的时间戳都比它大
+    pub fn observe(&mut self, remote: Hlc) -> Result<()> {
+        if remote <= self.last {
+            return Ok(());
+        }
+        self.last = remote;
+        self.save()
+    }
+
+    fn save(&self) -> Result<()> {
+        write_clock_file(&self.path, &self.last)
+    }
+}
+
+fn write_clock_file(path: &Path, hlc: &Hlc) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let content = serde_json::to_string(hlc)?;
+    let temp_path = path.with_extension("tmp");
+    std::fs::write(&temp_path, content).context("Failed to save sync clock")?;
+    std::fs::rename(&temp_path, path)?;
+    Ok(())
+}
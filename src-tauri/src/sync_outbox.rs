@@ -0,0 +1,171 @@
+use anyhow::{ensure, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+
+use crate::sync::Operation;
+use crate::sync_clock::Hlc;
+
+/// 发件箱文件名，保存在应用数据目录中
+pub const OUTBOX_FILE_NAME: &str = "sync_outbox.json";
+
+/// 发件箱中等待上传的操作
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutboxEntry {
+    pub op: Operation,
+    /// 操作引用的 blob，需要在操作之前上传
+    #[serde(default)]
+    pub blobs: Vec<String>,
+    /// 已经失败的上传次数
+    #[serde(default)]
+    pub attempts: u32,
+    #[serde(default)]
+    pub last_error: Option<String>,
+}
+
+/// 本地发件箱，记录尚未确认写入对象存储的同步操作
+///
+/// 操作在上传之前先写入磁盘，只有对象存储确认写入后才删除，
+/// 因此网络故障或应用重启都不会丢失操作。按加入顺序上传。
+pub struct Outbox {
+    path: PathBuf,
+    entries: VecDeque<OutboxEntry>,
+}
+
+impl Outbox {
+    /// 加载发件箱；文件损坏时另存为 .bad 文件并从空发件箱开始
+    pub fn load(path: PathBuf) -> Self {
+        let entries = match std::fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                tracing::warn!("解析同步发件箱失败，已另存为 .bad 文件: {}", e);
+                let _ = std::fs::rename(&path, path.with_extension("bad"));
+                VecDeque::new()
+            }),
+            Err(_) => VecDeque::new(),
+        };
+        Self { path, entries }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// 最早加入、下一批要上传的操作，最多 max 个
+    pub fn peek(&self, max: usize) -> Vec<OutboxEntry> {
+        self.entries.iter().take(max).cloned().collect()
+    }
+
+    /// 最近一次上传失败的原因
+    pub fn last_error(&self) -> Option<&str> {
+        self.entries.iter().find_map(|entry| entry.last_error.as_deref())
+    }
+
+    /// 最后加入的操作的时钟
+    pub fn last_clock(&self) -> Option<Hlc> {
+        self.entries.back().map(|entry| entry.op.clock())
+    }
+
+    /// 加入操作并立即写入磁盘
+    ///
+    /// 操作的时钟必须大于发件箱中所有操作的时钟：操作按加入顺序分段上传，
+    /// 其他设备读到一个段后游标前进到段中最大的时钟，时钟更小的操作之后会被跳过。
+    pub fn push(&mut self, op: Operation, blobs: Vec<String>) -> Result<()> {
+        if let Some(last_clock) = self.last_clock() {
+            ensure!(op.clock() > last_clock, "操作的时钟 {} 不大于发件箱中最后一个操作的时钟 {}", op.clock(), last_clock);
+        }
+        self.entries.push_back(OutboxEntry {
+            op,
+            blobs,
+            attempts: 0,
+            last_error: None,
+        });
+        self.save()
+    }
+
+    /// 操作已确认写入对象存储，从发件箱删除
+    pub fn remove(&mut self, op_ids: &[String]) -> Result<()> {
+        self.entries.retain(|entry| !op_ids.contains(&entry.op.op_id));
+        self.save()
+    }
+
+    /// 记录一次上传失败
+    pub fn record_failure(&mut self, op_ids: &[String], error: &str) -> Result<()> {
+        for entry in self.entries.iter_mut().filter(|entry| op_ids.contains(&entry.op.op_id)) {
+            entry.attempts += 1;
+            entry.last_error = Some(error.to_string());
+        }
+        self.save()
+    }
+
+    fn save(&self) -> Result<()> {
+        write_outbox_file(&self.path, &self.entries)
+    }
+}
+
+/// 发件箱中的操作引用的 blob，这些 blob 上传之前不能删除
+///
+/// 只读取文件，不会像 [`Outbox::load`] 那样处理损坏的文件；文件不存在时为空
+pub fn referenced_blobs(path: &Path) -> Result<HashSet<String>> {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(HashSet::new()),
+        Err(e) => return Err(e).context("Failed to read sync outbox"),
+    };
+    let entries: Vec<OutboxEntry> = serde_json::from_str(&content).context("Failed to parse sync outbox")?;
+    Ok(entries.into_iter().flat_map(|entry| entry.blobs).collect())
+}
+
+fn write_outbox_file(path: &Path, entries: &VecDeque<OutboxEntry>) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let content = serde_json::to_string(entries)?;
+    // 先写临时文件再重命名，避免写入中途崩溃损坏发件箱
+    let temp_path = path.with_extension("tmp");
+    std::fs::write(&temp_path, content).context("Failed to save sync outbox")?;
+    std::fs::rename(&temp_path, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn delete_op(wall_ms: i64) -> Operation {
+        Operation::new_delete("item".to_string(), "device".to_string(), Hlc { wall_ms, counter: 0 })
+    }
+
+    #[test]
+    fn rejects_operations_out_of_clock_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sync_outbox.json");
+        let mut outbox = Outbox::load(path.clone());
+
+        outbox.push(delete_op(2), Vec::new()).unwrap();
+        assert!(outbox.push(delete_op(2), Vec::new()).is_err());
+        assert!(outbox.push(delete_op(1), Vec::new()).is_err());
+        outbox.push(delete_op(3), Vec::new()).unwrap();
+
+        let reloaded = Outbox::load(path);
+        assert_eq!(reloaded.len(), 2);
+        assert_eq!(reloaded.last_clock(), Some(Hlc { wall_ms: 3, counter: 0 }));
+    }
+
+    #[test]
+    fn referenced_blobs_reads_pending_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(OUTBOX_FILE_NAME);
+        assert!(referenced_blobs(&path).unwrap().is_empty());
+
+        let mut outbox = Outbox::load(path.clone());
+        outbox.push(delete_op(1), vec!["hash-a".to_string(), "hash-b".to_string()]).unwrap();
+        outbox.push(delete_op(2), vec!["hash-a".to_string()]).unwrap();
+        let expected = HashSet::from(["hash-a".to_string(), "hash-b".to_string()]);
+        assert_eq!(referenced_blobs(&path).unwrap(), expected);
+
+        // 损坏的文件返回错误，并且不会被改名
+        std::fs::write(&path, "{ not json").unwrap();
+        assert!(referenced_blobs(&path).is_err());
+        assert!(path.exists());
+    }
+}
@@ -0,0 +1,328 @@
+use serde::{Deserialize, Serialize};
+
+/// 超过这个长度的文本只做 JSON 和代码识别
+const MAX_TOKEN_LEN: usize = 2048;
+
+/// 文本内容的识别结果
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DetectedKind {
+    Url,
+    Email,
+    Path,
+    /// 颜色值，统一为 #rrggbb 或 #rrggbbaa
+    Color { hex: String },
+    Json,
+    /// 代码片段，language 为猜测的语言
+    Code { language: String },
+    Number,
+    Phone,
+    Uuid,
+}
+
+impl DetectedKind {
+    /// 类型名称，与序列化后的 kind 字段一致
+    pub fn name(&self) -> &'static str {
+        match self {
+            DetectedKind::Url => "url",
+            DetectedKind::Email => "email",
+            DetectedKind::Path => "path",
+            DetectedKind::Color { .. } => "color",
+            DetectedKind::Json => "json",
+            DetectedKind::Code { .. } => "code",
+            DetectedKind::Number => "number",
+            DetectedKind::Phone => "phone",
+            DetectedKind::Uuid => "uuid",
+        }
+    }
+}
+
+/// 识别文本内容的类型，无法识别时返回 None
+pub fn classify(text: &str) -> Option<DetectedKind> {
+    let text = text.trim();
+    if text.is_empty() {
+        return None;
+    }
+
+    // 单个词的类型：不含空白，且不会太长
+    if text.len() <= MAX_TOKEN_LEN && !text.contains(char::is_whitespace) {
+        if is_uuid(text) {
+            return Some(DetectedKind::Uuid);
+        }
+        if let Some(hex) = parse_color(text) {
+            return Some(DetectedKind::Color { hex });
+        }
+        if is_number(text) {
+            return Some(DetectedKind::Number);
+        }
+        if is_url(text) {
+            return Some(DetectedKind::Url);
+        }
+        if is_email(text) {
+            return Some(DetectedKind::Email);
+        }
+    }
+
+    if text.len() <= MAX_TOKEN_LEN && !text.contains('\n') {
+        if let Some(hex) = parse_color(text) {
+            return Some(DetectedKind::Color { hex });
+        }
+        if is_phone(text) {
+            return Some(DetectedKind::Phone);
+        }
+        if is_path(text) {
+            return Some(DetectedKind::Path);
+        }
+    }
+
+    if is_json(text) {
+        return Some(DetectedKind::Json);
+    }
+
+    guess_code_language(text).map(|language| DetectedKind::Code {
+        language: language.to_string(),
+    })
+}
+
+fn is_uuid(text: &str) -> bool {
+    let groups: Vec<&str> = text.split('-').collect();
+    groups.len() == 5
+        && groups
+            .iter()
+            .zip([8, 4, 4, 4, 12])
+            .all(|(group, len)| group.len() == len && group.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+// 支持 #rgb、#rgba、#rrggbb、#rrggbbaa、rgb(r, g, b) 和 rgba(r, g, b, a)
+fn parse_color(text: &str) -> Option<String> {
+    if let Some(hex) = text.strip_prefix('#') {
+        if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        let hex = hex.to_lowercase();
+        return match hex.len() {
+            3 | 4 => Some(format!("#{}", hex.chars().flat_map(|c| [c, c]).collect::<String>())),
+            6 | 8 => Some(format!("#{}", hex)),
+            _ => None,
+        };
+    }
+
+    let lower = text.to_lowercase();
+    let args = lower
+        .strip_prefix("rgba(")
+        .or_else(|| lower.strip_prefix("rgb("))?
+        .strip_suffix(')')?;
+    let parts: Vec<&str> = args.split(',').map(str::trim).collect();
+    if parts.len() != 3 && parts.len() != 4 {
+        return None;
+    }
+
+    let mut hex = String::from("#");
+    for part in &parts[..3] {
+        let value: u8 = part.parse().ok()?;
+        hex.push_str(&format!("{:02x}", value));
+    }
+    if let Some(alpha) = parts.get(3) {
+        let alpha: f64 = alpha.parse().ok()?;
+        if !(0.0..=1.0).contains(&alpha) {
+            return None;
+        }
+        hex.push_str(&format!("{:02x}", (alpha * 255.0).round() as u8));
+    }
+    Some(hex)
+}
+
+// 整数、小数、科学计数法，允许千位分隔符
+fn is_number(text: &str) -> bool {
+    let digits = text.replace([',', '_'], "");
+    digits.chars().any(|c| c.is_ascii_digit())
+        && digits.chars().all(|c| c.is_ascii_digit() || matches!(c, '.' | '-' | '+' | 'e' | 'E'))
+        && digits.parse::<f64>().is_ok()
+}
+
+fn is_url(text: &str) -> bool {
+    if let Some((scheme, rest)) = text.split_once("://") {
+        return matches!(scheme.to_lowercase().as_str(), "http" | "https" | "ftp" | "ftps" | "ws" | "wss" | "file")
+            && !rest.is_empty();
+    }
+    text.to_lowercase().starts_with("www.") && text.len() > 4 && text[4..].contains('.')
+}
+
+fn is_email(text: &str) -> bool {
+    let Some((local, domain)) = text.split_once('@') else {
+        return false;
+    };
+    let valid_local = !local.is_empty()
+        && local.chars().all(|c| c.is_alphanumeric() || "._%+-".contains(c));
+    let valid_domain = domain.contains('.')
+        && !domain.starts_with('.')
+        && !domain.ends_with('.')
+        && domain.chars().all(|c| c.is_alphanumeric() || c == '.' || c == '-');
+    valid_local && valid_domain
+}
+
+// 国际号码带 + 前缀，或包含空格、横线、括号等分隔符，数字位数在 7 到 15 位之间；
+// 排除 2024-01-31、31.01.2024 这样的日期。点号只在同时有 + 前缀、空格或括号时才算分隔符，
+// 否则 192.168.100.200 这样的 IP 地址和版本号也会被当成电话号码
+fn is_phone(text: &str) -> bool {
+    if is_date(text) {
+        return false;
+    }
+
+    let body = text.strip_prefix('+').unwrap_or(text);
+    if !body.chars().all(|c| c.is_ascii_digit() || matches!(c, ' ' | '-' | '(' | ')' | '.')) {
+        return false;
+    }
+    let dots_allowed = text.starts_with('+') || body.contains([' ', '(', ')']);
+    if body.contains('.') && !dots_allowed {
+        return false;
+    }
+    let digit_count = body.chars().filter(|c| c.is_ascii_digit()).count();
+    let has_separator = body.chars().any(|c| !c.is_ascii_digit());
+    (7..=15).contains(&digit_count) && (text.starts_with('+') || has_separator)
+}
+
+// 年月日或日月年，以 - . / 分隔，年份为 4 位
+fn is_date(text: &str) -> bool {
+    let groups: Vec<&str> = text.split(['-', '.', '/']).collect();
+    if groups.len() != 3 || !groups.iter().all(|group| !group.is_empty() && group.chars().all(|c| c.is_ascii_digit())) {
+        return false;
+    }
+    let lens: Vec<usize> = groups.iter().map(|group| group.len()).collect();
+    matches!(lens[..], [4, 1..=2, 1..=2] | [1..=2, 1..=2, 4])
+}
+
+fn is_path(text: &str) -> bool {
+    if text.contains("://") || text.len() < 2 {
+        return false;
+    }
+    let bytes = text.as_bytes();
+    let windows_drive = bytes.len() >= 3
+        && bytes[0].is_ascii_alphabetic()
+        && bytes[1] == b':'
+        && (bytes[2] == b'\\' || bytes[2] == b'/');
+
+    windows_drive
+        || text.starts_with("\\\\")
+        || text.starts_with("~/")
+        || text.starts_with("./")
+        || text.starts_with("../")
+        || (text.starts_with('/') && !text.starts_with("//") && text[1..].chars().next().is_some_and(|c| c != ' '))
+}
+
+fn is_json(text: &str) -> bool {
+    let looks_like_json = (text.starts_with('{') && text.ends_with('}'))
+        || (text.starts_with('[') && text.ends_with(']'));
+    looks_like_json && serde_json::from_str::<serde_json::Value>(text).is_ok()
+}
+
+// 各语言的特征片段，按命中次数猜测语言
+const LANGUAGE_HINTS: &[(&str, &[&str])] = &[
+    ("rust", &["fn ", "let mut ", "impl ", "pub fn ", "use std::", "::new(", "-> ", "&mut ", "match ", "Some(", "Ok("]),
+    ("python", &["def ", "import ", "from ", "self.", "elif ", "print(", "None", "__init__", "lambda "]),
+    ("javascript", &["function ", "const ", "let ", "=> ", "console.log", "require(", "export ", "document."]),
+    ("typescript", &["interface ", ": string", ": number", "export type ", "as const", "readonly "]),
+    ("go", &["func ", "package ", ":= ", "fmt.", "go ", "chan ", "defer "]),
+    ("java", &["public class ", "private ", "System.out", "public static void", "@Override", "new "]),
+    ("c", &["#include", "int main(", "printf(", "malloc(", "NULL", "->"]),
+    ("cpp", &["std::", "#include <", "cout <<", "template<", "nullptr", "namespace "]),
+    ("sql", &["SELECT ", "FROM ", "WHERE ", "INSERT INTO", "UPDATE ", "CREATE TABLE", "JOIN "]),
+    ("shell", &["#!/bin/", "echo ", "sudo ", "export ", "$(", "fi\n", "done\n", "| grep"]),
+    ("html", &["<!DOCTYPE", "<html", "<div", "</div>", "<span", "class=\""]),
+    ("css", &["{\n", "color:", "margin:", "padding:", "display:", "font-size:"]),
+];
+
+// 至少命中两个特征，且文本中有代码常见的标点，才认为是代码
+fn guess_code_language(text: &str) -> Option<&'static str> {
+    let has_code_punctuation = text.contains(';')
+        || text.contains('{')
+        || text.contains("()")
+        || text.contains(":\n")
+        || text.contains("</");
+    if !has_code_punctuation {
+        return None;
+    }
+
+    LANGUAGE_HINTS
+        .iter()
+        .map(|(language, hints)| {
+            let hits = hints.iter().filter(|hint| text.contains(*hint)).count();
+            (*language, hits)
+        })
+        .filter(|(_, hits)| *hits >= 2)
+        .max_by_key(|(_, hits)| *hits)
+        .map(|(language, _)| language)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn code(language: &str) -> Option<DetectedKind> {
+        Some(DetectedKind::Code { language: language.to_string() })
+    }
+
+    fn color(hex: &str) -> Option<DetectedKind> {
+        Some(DetectedKind::Color { hex: hex.to_string() })
+    }
+
+    #[test]
+    fn classifies_each_kind() {
+        let cases = [
+            ("https://example.com/path?q=1", Some(DetectedKind::Url)),
+            ("www.example.com", Some(DetectedKind::Url)),
+            ("someone@example.com", Some(DetectedKind::Email)),
+            ("/usr/local/bin", Some(DetectedKind::Path)),
+            ("C:\\Users\\me", Some(DetectedKind::Path)),
+            ("~/notes.txt", Some(DetectedKind::Path)),
+            ("#FFF", color("#ffffff")),
+            ("#11223344", color("#11223344")),
+            ("rgb(255, 0, 0)", color("#ff0000")),
+            ("rgba(0, 0, 0, 0.5)", color("#00000080")),
+            (r#"{"a": [1, 2]}"#, Some(DetectedKind::Json)),
+            ("fn main() {\n    let mut x = 1;\n}", code("rust")),
+            ("SELECT id FROM users WHERE id = 1;", code("sql")),
+            ("42", Some(DetectedKind::Number)),
+            ("-3.14", Some(DetectedKind::Number)),
+            ("1,234,567", Some(DetectedKind::Number)),
+            ("1e10", Some(DetectedKind::Number)),
+            ("+86 138 0013 8000", Some(DetectedKind::Phone)),
+            ("(555) 123-4567", Some(DetectedKind::Phone)),
+            ("415-555-2671", Some(DetectedKind::Phone)),
+            ("+1 (555) 123.4567", Some(DetectedKind::Phone)),
+            ("550e8400-e29b-41d4-a716-446655440000", Some(DetectedKind::Uuid)),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(classify(text), expected, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn ignores_known_false_positives() {
+        let cases = [
+            "",
+            "   ",
+            // 日期
+            "2024-01-31",
+            "31.01.2024",
+            "1/2/2024",
+            // IP 地址和版本号
+            "192.168.100.200",
+            "1.22.333.4444",
+            "10.0.0.1",
+            "1.2.3",
+            // 普通文字
+            "hello world",
+            "Meet me at 10:30 tomorrow",
+            "see you at the café; bring snacks",
+            "just a sentence with a slash/in it",
+            "#zzz",
+            "rgb(300, 0, 0)",
+            "{not json}",
+            "user@localhost",
+        ];
+        for text in cases {
+            assert_eq!(classify(text), None, "{:?}", text);
+        }
+    }
+}
@@ -0,0 +1,418 @@
+use base64::{engine::general_purpose, Engine as _};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// 单个文本转换步骤
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Transform {
+    /// 去掉首尾空白
+    Trim,
+    /// 去掉每行首尾空白，连续空白合并为一个空格，连续空行合并为一个
+    NormalizeWhitespace,
+    Uppercase,
+    Lowercase,
+    TitleCase,
+    SnakeCase,
+    CamelCase,
+    KebabCase,
+    UrlEncode,
+    UrlDecode,
+    Base64Encode,
+    Base64Decode,
+    JsonPretty,
+    JsonMinify,
+    SortLines,
+    DedupeLines,
+    StripAnsi,
+    StripMarkdown,
+}
+
+impl Transform {
+    /// 所有可用的转换
+    pub const ALL: [Transform; 18] = [
+        Transform::Trim,
+        Transform::NormalizeWhitespace,
+        Transform::Uppercase,
+        Transform::Lowercase,
+        Transform::TitleCase,
+        Transform::SnakeCase,
+        Transform::CamelCase,
+        Transform::KebabCase,
+        Transform::UrlEncode,
+        Transform::UrlDecode,
+        Transform::Base64Encode,
+        Transform::Base64Decode,
+        Transform::JsonPretty,
+        Transform::JsonMinify,
+        Transform::SortLines,
+        Transform::DedupeLines,
+        Transform::StripAnsi,
+        Transform::StripMarkdown,
+    ];
+
+    pub fn apply(self, text: &str) -> Result<String, String> {
+        Ok(match self {
+            Transform::Trim => text.trim().to_string(),
+            Transform::NormalizeWhitespace => normalize_whitespace(text),
+            Transform::Uppercase => text.to_uppercase(),
+            Transform::Lowercase => text.to_lowercase(),
+            Transform::TitleCase => split_words(text)
+                .iter()
+                .map(|word| capitalize(word))
+                .collect::<Vec<_>>()
+                .join(" "),
+            Transform::SnakeCase => split_words(text)
+                .iter()
+                .map(|word| word.to_lowercase())
+                .collect::<Vec<_>>()
+                .join("_"),
+            Transform::KebabCase => split_words(text)
+                .iter()
+                .map(|word| word.to_lowercase())
+                .collect::<Vec<_>>()
+                .join("-"),
+            Transform::CamelCase => split_words(text)
+                .iter()
+                .enumerate()
+                .map(|(i, word)| if i == 0 { word.to_lowercase() } else { capitalize(word) })
+                .collect(),
+            Transform::UrlEncode => url_encode(text),
+            Transform::UrlDecode => url_decode(text)?,
+            Transform::Base64Encode => general_purpose::STANDARD.encode(text.as_bytes()),
+            Transform::Base64Decode => {
+                let bytes = general_purpose::STANDARD
+                    .decode(text.trim())
+                    .map_err(|e| format!("base64 解码失败: {}", e))?;
+                String::from_utf8(bytes).map_err(|_| "解码结果不是有效的 UTF-8 文本".to_string())?
+            }
+            Transform::JsonPretty => {
+                let value = parse_json(text)?;
+                serde_json::to_string_pretty(&value).map_err(|e| e.to_string())?
+            }
+            Transform::JsonMinify => {
+                let value = parse_json(text)?;
+                serde_json::to_string(&value).map_err(|e| e.to_string())?
+            }
+            Transform::SortLines => {
+                let mut lines: Vec<&str> = text.lines().collect();
+                lines.sort();
+                join_lines(text, lines)
+            }
+            Transform::DedupeLines => {
+                let mut seen = HashSet::new();
+                join_lines(text, text.lines().filter(|line| seen.insert(*line)).collect())
+            }
+            Transform::StripAnsi => strip_ansi(text),
+            Transform::StripMarkdown => strip_markdown(text),
+        })
+    }
+}
+
+/// 命名的转换链，保存在设置中
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransformChain {
+    pub name: String,
+    pub steps: Vec<Transform>,
+}
+
+/// 转换结果的去向
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransformOutput {
+    /// 只返回结果
+    Preview,
+    /// 写入剪贴板
+    Copy,
+    /// 作为新项目加入历史
+    Insert,
+}
+
+/// 依次执行多个转换
+pub fn apply_chain(text: &str, steps: &[Transform]) -> Result<String, String> {
+    steps
+        .iter()
+        .try_fold(text.to_string(), |text, step| step.apply(&text))
+}
+
+fn normalize_whitespace(text: &str) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        let line = line.split_whitespace().collect::<Vec<_>>().join(" ");
+        if line.is_empty() && lines.last().is_none_or(|last| last.is_empty()) {
+            continue;
+        }
+        lines.push(line);
+    }
+    while lines.last().is_some_and(|last| last.is_empty()) {
+        lines.pop();
+    }
+    lines.join("\n")
+}
+
+// 按行重新拼接，保留原文末尾的换行
+fn join_lines(text: &str, lines: Vec<&str>) -> String {
+    let mut joined = lines.join("\n");
+    if text.ends_with('\n') {
+        joined.push('\n');
+    }
+    joined
+}
+
+// 按非字母数字字符和驼峰边界拆分单词；连续大写的缩写在最后一个大写字母前拆开，
+// 例如 HTTPServer 拆为 HTTP 和 Server
+fn split_words(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut prev: Option<char> = None;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if !c.is_alphanumeric() {
+            if !current.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+            prev = None;
+            continue;
+        }
+        let next_is_lowercase = chars.peek().is_some_and(|next| next.is_lowercase());
+        let boundary = c.is_uppercase()
+            && prev.is_some_and(|p| p.is_lowercase() || p.is_ascii_digit() || (p.is_uppercase() && next_is_lowercase));
+        if boundary && !current.is_empty() {
+            words.push(std::mem::take(&mut current));
+        }
+        current.push(c);
+        prev = Some(c);
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    words
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars.flat_map(char::to_lowercase)).collect(),
+        None => String::new(),
+    }
+}
+
+// 按 RFC 3986 编码，保留非保留字符
+fn url_encode(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'.' | b'~') {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+fn url_decode(text: &str) -> Result<String, String> {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let byte = text
+                .get(i + 1..i + 3)
+                // from_str_radix 接受 + 号，需要先检查两位都是十六进制数字
+                .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .ok_or_else(|| format!("无效的 URL 编码: 位置 {}", i))?;
+            decoded.push(byte);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).map_err(|_| "解码结果不是有效的 UTF-8 文本".to_string())
+}
+
+fn parse_json(text: &str) -> Result<serde_json::Value, String> {
+    serde_json::from_str(text.trim()).map_err(|e| format!("JSON 解析失败: {}", e))
+}
+
+// 去掉 CSI（ESC [ ... 终止字符）和 OSC（ESC ] ... BEL 或 ESC \）控制序列
+fn strip_ansi(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '\u{1b}' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('\u{40}'..='\u{7e}').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\u{7}' {
+                        break;
+                    }
+                    if c == '\u{1b}' && chars.peek() == Some(&'\\') {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            // 其他两字符转义序列
+            _ => {}
+        }
+    }
+    result
+}
+
+// 去掉常见的 Markdown 标记，保留文字内容
+fn strip_markdown(text: &str) -> String {
+    text.lines()
+        .filter(|line| !line.trim_start().starts_with("```"))
+        .map(|line| strip_inline_markdown(strip_block_markers(line)))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// 标题、引用和列表标记
+fn strip_block_markers(line: &str) -> &str {
+    let trimmed = line.trim_start();
+    let trimmed = trimmed.trim_start_matches('>').trim_start();
+
+    if let Some(rest) = trimmed.strip_prefix('#') {
+        return rest.trim_start_matches('#').trim_start();
+    }
+    for marker in ["- [ ] ", "- [x] ", "- ", "* ", "+ "] {
+        if let Some(rest) = trimmed.strip_prefix(marker) {
+            return rest;
+        }
+    }
+    let digits = trimmed.chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits > 0 {
+        if let Some(rest) = trimmed[digits..].strip_prefix(". ") {
+            return rest;
+        }
+    }
+    trimmed
+}
+
+// 链接和图片保留文字，去掉强调和行内代码标记
+fn strip_inline_markdown(line: &str) -> String {
+    let mut result = String::with_capacity(line.len());
+    let mut rest = line;
+
+    while let Some(start) = rest.find('[') {
+        let (before, after) = rest.split_at(start);
+        let link = after
+            .find("](")
+            .and_then(|mid| after[mid..].find(')').map(|end| (mid, mid + end)));
+        match link {
+            Some((mid, end)) => {
+                result.push_str(before.strip_suffix('!').unwrap_or(before));
+                result.push_str(&after[1..mid]);
+                rest = &after[end + 1..];
+            }
+            None => {
+                result.push_str(before);
+                result.push('[');
+                rest = &after[1..];
+            }
+        }
+    }
+    result.push_str(rest);
+
+    result.replace("**", "").replace("__", "").replace("~~", "").replace('`', "")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(transform: Transform, text: &str) -> String {
+        transform.apply(text).unwrap()
+    }
+
+    #[test]
+    fn whitespace_transforms() {
+        assert_eq!(apply(Transform::Trim, "  hi there \n"), "hi there");
+        assert_eq!(apply(Transform::NormalizeWhitespace, "  a \t  b \n\n\n  c  \n\n"), "a b\n\nc");
+    }
+
+    #[test]
+    fn case_transforms() {
+        assert_eq!(apply(Transform::Uppercase, "Straße"), "STRASSE");
+        assert_eq!(apply(Transform::Lowercase, "HeLLo"), "hello");
+        assert_eq!(apply(Transform::TitleCase, "hello_world HTTPServer"), "Hello World Http Server");
+        assert_eq!(apply(Transform::SnakeCase, "parseHTTPResponse2xx"), "parse_http_response2xx");
+        assert_eq!(apply(Transform::SnakeCase, "version2Beta"), "version2_beta");
+        assert_eq!(apply(Transform::CamelCase, "user id-value"), "userIdValue");
+        assert_eq!(apply(Transform::CamelCase, "HTTPServer"), "httpServer");
+        assert_eq!(apply(Transform::KebabCase, "XMLHttpRequest"), "xml-http-request");
+        assert_eq!(apply(Transform::KebabCase, "ALL CAPS"), "all-caps");
+    }
+
+    #[test]
+    fn url_round_trip() {
+        let encoded = apply(Transform::UrlEncode, "a b/ü~");
+        assert_eq!(encoded, "a%20b%2F%C3%BC~");
+        assert_eq!(apply(Transform::UrlDecode, &encoded), "a b/ü~");
+        assert_eq!(apply(Transform::UrlDecode, "%e4%b8%ad"), "中");
+
+        for invalid in ["%+F", "%-1", "%4", "%zz", "%FF"] {
+            assert!(Transform::UrlDecode.apply(invalid).is_err(), "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn base64_round_trip() {
+        let encoded = apply(Transform::Base64Encode, "héllo");
+        assert_eq!(encoded, "aMOpbGxv");
+        assert_eq!(apply(Transform::Base64Decode, &format!(" {}\n", encoded)), "héllo");
+        assert!(Transform::Base64Decode.apply("not base64!").is_err());
+        // 0xFF 不是有效的 UTF-8
+        assert!(Transform::Base64Decode.apply("/w==").is_err());
+    }
+
+    #[test]
+    fn json_transforms() {
+        let pretty = apply(Transform::JsonPretty, r#"{"a":[1,2]}"#);
+        assert_eq!(pretty, "{\n  \"a\": [\n    1,\n    2\n  ]\n}");
+        assert_eq!(apply(Transform::JsonMinify, &pretty), r#"{"a":[1,2]}"#);
+        assert!(Transform::JsonPretty.apply("{").is_err());
+        assert!(Transform::JsonMinify.apply("not json").is_err());
+    }
+
+    #[test]
+    fn line_transforms_keep_trailing_newline() {
+        assert_eq!(apply(Transform::SortLines, "b\na\nc\n"), "a\nb\nc\n");
+        assert_eq!(apply(Transform::SortLines, "b\na"), "a\nb");
+        assert_eq!(apply(Transform::DedupeLines, "a\nb\na\n"), "a\nb\n");
+        assert_eq!(apply(Transform::DedupeLines, "a\na"), "a");
+    }
+
+    #[test]
+    fn strip_transforms() {
+        let ansi = "\u{1b}[1;31mred\u{1b}[0m \u{1b}]8;;http://x\u{7}link\u{1b}]8;;\u{1b}\\";
+        assert_eq!(apply(Transform::StripAnsi, ansi), "red link");
+
+        let markdown = "# Title\n> - [x] **done** with `code`\n```rust\nlet x;\n```\n1. see [docs](http://a) ![img](b.png)";
+        assert_eq!(apply(Transform::StripMarkdown, markdown), "Title\ndone with code\nlet x;\nsee docs img");
+    }
+
+    #[test]
+    fn chain_applies_steps_in_order() {
+        let steps = [Transform::Trim, Transform::SnakeCase, Transform::Uppercase];
+        assert_eq!(apply_chain("  Hello World  ", &steps).unwrap(), "HELLO_WORLD");
+        assert_eq!(apply_chain("unchanged", &[]).unwrap(), "unchanged");
+        // 任何一步失败时整个链失败
+        assert!(apply_chain("aGk=", &[Transform::Base64Decode, Transform::Base64Decode]).is_err());
+    }
+}
@@ -11,6 +11,12 @@ export interface FileTypeInfo {
   file_type: string;
   mime_type: string;
   category: string;
+  size?: number;
+  is_dir?: boolean;
+  is_symlink?: boolean;
+  entry_count?: number;
+  truncated?: boolean;
+  snapshot?: string;
 }
 
 export interface ClipboardItem {
@@ -21,6 +27,11 @@ export interface ClipboardItem {
   size?: number;
   file_paths?: string[];
   file_types?: FileTypeInfo[];
+  detected_kind?: { kind: string; hex?: string; language?: string };
+  tags?: string[];
+  pinned?: boolean;
+  expires_at?: number;
+  exclude_from_sync?: boolean;
 }
 
 export interface StorageStats {
@@ -63,8 +74,27 @@ function App() {
       setClipboardHistory((prev) => [event.payload, ...prev.slice(0, 99)]);
     });
 
+    // 监听到期项目被删除的事件
+    const unlistenRemoved = listen<string[]>("clipboard-items-removed", (event) => {
+      const removed = new Set(event.payload);
+      setClipboardHistory((prev) => prev.filter((item) => !removed.has(item.id)));
+    });
+
+    // 监听其他设备同步过来的变化
+    const unlistenSynced = listen<{ added: ClipboardItem[]; removed: string[] }>("sync-items-changed", (event) => {
+      const { added, removed } = event.payload;
+      const changed = new Set([...removed, ...added.map((item) => item.id)]);
+      setClipboardHistory((prev) =>
+        [...added, ...prev.filter((item) => !changed.has(item.id))]
+          .sort((a, b) => b.timestamp - a.timestamp)
+          .slice(0, 100)
+      );
+    });
+
     return () => {
       unlisten.then((fn) => fn());
+      unlistenRemoved.then((fn) => fn());
+      unlistenSynced.then((fn) => fn());
     };
   }, []);
 
@@ -27,6 +27,8 @@ interface StorageConfig {
 interface SyncStatus {
   item_count: number;
   is_syncing: boolean;
+  outbox_depth?: number;
+  last_upload_error?: string | null;
 }
 
 export const StorageConfig: React.FC = () => {
@@ -544,6 +546,17 @@ export const StorageConfig: React.FC = () => {
                         <div className="text-sm text-muted-foreground">同步状态</div>
                       </CardContent>
                     </Card>
+                    {!!syncStatus.outbox_depth && (
+                      <Card className="col-span-2">
+                        <CardContent className="p-4">
+                          <div className="text-2xl font-bold">{syncStatus.outbox_depth}</div>
+                          <div className="text-sm text-muted-foreground">待上传操作</div>
+                          {syncStatus.last_upload_error && (
+                            <div className="text-sm text-red-600 mt-1">{syncStatus.last_upload_error}</div>
+                          )}
+                        </CardContent>
+                      </Card>
+                    )}
                   </div>
                 )}
                 